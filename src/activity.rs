@@ -0,0 +1,166 @@
+use crate::{
+    simulate::{RandomWordGenerator, SimdSimulationWord, Simulation, SimulationWord},
+    Aig, AigEdge, AigNodeId,
+};
+
+/// bits of precision of the input probabilities.
+const ACTIVITY_PROBABILITY_BITS: usize = 16;
+
+/// How the inputs and latch inputs are driven when estimating activity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityStimulus {
+    /// independent random values in every cycle, latch inputs included.
+    Random,
+    /// parallel traces from the init state with random inputs.
+    Sequential,
+}
+
+/// Per node estimation of the probability of being 1 and of the switching
+/// activity, the probability that the value changes between two cycles.
+#[derive(Debug, Clone)]
+pub struct SignalActivity {
+    probabilities: Vec<f64>,
+    toggle_rates: Vec<f64>,
+}
+
+impl SignalActivity {
+    pub fn probability(&self, id: AigNodeId) -> f64 {
+        self.probabilities[id]
+    }
+
+    pub fn toggle_rate(&self, id: AigNodeId) -> f64 {
+        self.toggle_rates[id]
+    }
+
+    pub fn probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    pub fn toggle_rates(&self) -> &[f64] {
+        &self.toggle_rates
+    }
+}
+
+/// a random word whose bits are 1 with the probability, built from its
+/// binary expansion starting at the least significant bit.
+fn biased_word(gen: &mut RandomWordGenerator, probability: f64) -> SimulationWord {
+    let scale = 1u64 << ACTIVITY_PROBABILITY_BITS;
+    let scaled = (probability.clamp(0.0, 1.0) * scale as f64).round() as u64;
+    if scaled >= scale {
+        return !0;
+    }
+    let mut word = 0;
+    for bit in 0..ACTIVITY_PROBABILITY_BITS {
+        let rand = gen.rand_word();
+        word = if scaled >> bit & 1 == 1 {
+            word | rand
+        } else {
+            word & rand
+        };
+    }
+    word
+}
+
+fn edge_word(simulation: &Simulation, edge: AigEdge, idx: usize) -> SimulationWord {
+    let word = simulation[edge.node_id()][idx];
+    if edge.compl() {
+        !word
+    } else {
+        word
+    }
+}
+
+impl Aig {
+    /// Estimate the signal probability and toggle rate of every node by
+    /// simulating `nsimd_word` simd words of patterns for `cycles` cycles.
+    /// Input i is 1 with probability `input_probabilities[i]`, latch inputs
+    /// follow the stimulus.
+    pub fn signal_activity(
+        &mut self,
+        input_probabilities: &[f64],
+        stimulus: ActivityStimulus,
+        nsimd_word: usize,
+        cycles: usize,
+    ) -> SignalActivity {
+        assert_eq!(input_probabilities.len(), self.inputs.len());
+        assert!(cycles > 0);
+        let mut gen = self.new_word_generator();
+        let nword = nsimd_word * SimdSimulationWord::LANES;
+        let mut probability = vec![0.5; self.num_nodes()];
+        for (input, p) in self.inputs.iter().zip(input_probabilities.iter()) {
+            probability[*input] = *p;
+        }
+        let mut state: Vec<Option<Vec<SimulationWord>>> = vec![None; self.num_nodes()];
+        if stimulus == ActivityStimulus::Sequential {
+            for latch in &self.latchs {
+                state[latch.input] = Some(vec![if latch.init { !0 } else { 0 }; nword]);
+            }
+        }
+        let mut ones = vec![0u64; self.num_nodes()];
+        let mut toggles = vec![0u64; self.num_nodes()];
+        let mut prev: Option<Simulation> = None;
+        for _ in 0..cycles {
+            let simulation = self.simulation_from_words(nsimd_word, |id| match &state[id] {
+                Some(words) => words.clone(),
+                None => (0..nword)
+                    .map(|_| biased_word(&mut gen, probability[id]))
+                    .collect(),
+            });
+            for id in self.nodes_range_with_true() {
+                for i in 0..nword {
+                    ones[id] += simulation[id][i].count_ones() as u64;
+                    if let Some(prev) = &prev {
+                        toggles[id] += (simulation[id][i] ^ prev[id][i]).count_ones() as u64;
+                    }
+                }
+            }
+            if stimulus == ActivityStimulus::Sequential {
+                for latch in &self.latchs {
+                    state[latch.input] = Some(
+                        (0..nword)
+                            .map(|i| edge_word(&simulation, latch.next, i))
+                            .collect(),
+                    );
+                }
+            }
+            prev = Some(simulation);
+        }
+        let npattern = (nword * SimulationWord::BITS as usize) as f64;
+        SignalActivity {
+            probabilities: ones
+                .iter()
+                .map(|n| *n as f64 / (npattern * cycles as f64))
+                .collect(),
+            toggle_rates: toggles
+                .iter()
+                .map(|n| {
+                    if cycles > 1 {
+                        *n as f64 / (npattern * (cycles - 1) as f64)
+                    } else {
+                        0.0
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActivityStimulus;
+    use crate::Aig;
+
+    #[test]
+    fn test_signal_activity() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let activity = aig.signal_activity(&[0.9, 0.5, 0.1], ActivityStimulus::Random, 1, 4);
+        assert!((activity.probability(1) - 0.9).abs() < 0.02);
+        assert!((activity.toggle_rate(1) - 0.18).abs() < 0.02);
+        assert!((activity.probability(4) - 0.45).abs() < 0.02);
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        let activity = aig.signal_activity(&[1.0], ActivityStimulus::Sequential, 1, 8);
+        assert_eq!(activity.probability(2), 0.5);
+        assert_eq!(activity.toggle_rate(2), 1.0);
+        assert_eq!(activity.toggle_rate(3), 3.0 / 7.0);
+    }
+}
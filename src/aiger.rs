@@ -1,10 +1,71 @@
 use crate::{
     sat::{self},
+    simulate::{SimulationOptions, DEFAULT_RNG_SEED},
     Aig, AigEdge, AigLatch, AigNode,
 };
+use rand::{rngs::StdRng, SeedableRng};
 use std::{io, path::Path};
 
+/// Interface names from the AIGER symbol table, by position of the input,
+/// latch, output or bad state in the file.
+#[derive(Debug, Clone, Default)]
+pub struct AigSymbols {
+    pub inputs: Vec<Option<String>>,
+    pub latchs: Vec<Option<String>>,
+    pub outputs: Vec<Option<String>>,
+    pub bads: Vec<Option<String>>,
+}
+
+impl AigSymbols {
+    fn insert(list: &mut Vec<Option<String>>, position: usize, symbol: String) {
+        if list.len() <= position {
+            list.resize(position + 1, None);
+        }
+        list[position] = Some(symbol);
+    }
+
+    /// keep the symbols at the given increasing positions.
+    pub(crate) fn select(list: &mut Vec<Option<String>>, positions: &[usize]) {
+        *list = positions
+            .iter()
+            .map(|p| list.get(*p).cloned().flatten())
+            .collect();
+        while list.last().is_some_and(|s| s.is_none()) {
+            list.pop();
+        }
+    }
+
+    /// whether every symbol names an existing input, latch, output or bad
+    /// state of the design.
+    pub(crate) fn fits(&self, aig: &Aig) -> bool {
+        self.inputs.len() <= aig.inputs.len()
+            && self.latchs.len() <= aig.latchs.len()
+            && self.outputs.len() <= aig.outputs.len()
+            && self.bads.len() <= aig.bads.len()
+    }
+
+    pub fn input(&self, position: usize) -> Option<&str> {
+        self.inputs.get(position)?.as_deref()
+    }
+
+    pub fn latch(&self, position: usize) -> Option<&str> {
+        self.latchs.get(position)?.as_deref()
+    }
+
+    pub fn output(&self, position: usize) -> Option<&str> {
+        self.outputs.get(position)?.as_deref()
+    }
+
+    pub fn bad(&self, position: usize) -> Option<&str> {
+        self.bads.get(position)?.as_deref()
+    }
+}
+
 impl Aig {
+    pub fn symbols(&self) -> &AigSymbols {
+        &self.symbols
+    }
+
     fn setup_levels(&mut self) {
         let mut levels = vec![0; self.num_nodes()];
         for and in self.ands_iter() {
@@ -17,7 +78,7 @@ impl Aig {
         }
     }
 
-    fn setup_fanouts(&mut self) {
+    pub(crate) fn setup_fanouts(&mut self) {
         for id in self.nodes_range() {
             if self.nodes[id].is_and() {
                 let fanin0 = self.nodes[id].fanin0();
@@ -45,6 +106,7 @@ impl Aig {
         let mut bads = Vec::new();
         let mut inputs = Vec::new();
         let mut latchs = Vec::new();
+        let mut symbols = AigSymbols::default();
         for obj in aiger.records() {
             let obj = obj.unwrap();
             match obj {
@@ -78,10 +140,21 @@ impl Aig {
                     ));
                 }
                 aiger::Aiger::Symbol {
-                    type_spec: _,
-                    position: _,
-                    symbol: _,
-                } => (),
+                    type_spec,
+                    position,
+                    symbol,
+                } => {
+                    let list = match type_spec {
+                        aiger::Symbol::Input => &mut symbols.inputs,
+                        aiger::Symbol::Latch => &mut symbols.latchs,
+                        aiger::Symbol::Output => &mut symbols.outputs,
+                        aiger::Symbol::Bad => &mut symbols.bads,
+                        // constraints, justice and fairness properties are
+                        // not read, neither are their names.
+                        _ => continue,
+                    };
+                    AigSymbols::insert(list, position, symbol);
+                }
             }
         }
 
@@ -98,6 +171,9 @@ impl Aig {
             fraig: None,
             // sat_solver: Box::new(sat::minisat::Solver::new()),
             sat_solver: Box::new(sat::abc_glucose::Solver::new()),
+            rng: StdRng::seed_from_u64(DEFAULT_RNG_SEED),
+            symbols,
+            simulation_options: SimulationOptions::default(),
         };
         ret.setup_levels();
         ret.setup_fanouts();
@@ -0,0 +1,95 @@
+use crate::{map_edge, Aig, AigEdge, AigNode, AigNodeId};
+
+fn supergate(nodes: &[AigNode], refs: &[usize], root: AigNodeId) -> Vec<AigEdge> {
+    let mut leaves = Vec::new();
+    let mut stack = vec![nodes[root].fanin0(), nodes[root].fanin1()];
+    while let Some(edge) = stack.pop() {
+        let node = &nodes[edge.node_id()];
+        if !edge.compl() && node.is_and() && refs[edge.node_id()] == 1 {
+            stack.push(node.fanin0());
+            stack.push(node.fanin1());
+        } else {
+            leaves.push(edge);
+        }
+    }
+    leaves.sort_by_key(|e| (e.node_id(), e.compl()));
+    leaves.dedup();
+    leaves
+}
+
+impl Aig {
+    /// Rebuild every and supergate (multi-input and across non-complemented
+    /// single-fanout edges) as a tree balanced by level. The FRAIG state is
+    /// dropped.
+    pub fn balance(&mut self) {
+        let roots = self.roots();
+        let mut refs: Vec<usize> = self.nodes.iter().map(|n| n.fanouts.len()).collect();
+        for root in &roots {
+            refs[root.node_id()] += 1;
+        }
+        let (old_nodes, input_map) = self.take_nodes();
+        let mut map: Vec<Option<AigEdge>> = input_map
+            .iter()
+            .map(|i| i.map(|i| AigEdge::new(i, false)))
+            .collect();
+        let mut supergates: Vec<Option<Vec<AigEdge>>> = vec![None; old_nodes.len()];
+        for root in roots {
+            let mut stack = vec![root.node_id()];
+            while let Some(&id) = stack.last() {
+                if map[id].is_some() {
+                    stack.pop();
+                    continue;
+                }
+                let leaves = supergates[id].get_or_insert_with(|| supergate(&old_nodes, &refs, id));
+                let pending: Vec<AigNodeId> = leaves
+                    .iter()
+                    .map(|l| l.node_id())
+                    .filter(|l| map[*l].is_none())
+                    .collect();
+                if !pending.is_empty() {
+                    stack.extend(pending);
+                    continue;
+                }
+                let leaves = supergates[id].take().unwrap();
+                map[id] = Some(if leaves.windows(2).any(|w| w[0] == !w[1]) {
+                    AigEdge::constant_edge(false)
+                } else {
+                    let leaves: Vec<AigEdge> = leaves.iter().map(|l| map_edge(&map, *l)).collect();
+                    self.new_and_nodes(leaves)
+                });
+                stack.pop();
+            }
+        }
+        self.remap_interface(&input_map, &map);
+    }
+
+    pub fn depth(&self) -> usize {
+        self.nodes.iter().map(|n| n.level).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::random_output_values, Aig};
+
+    #[test]
+    fn test_balance_chain() {
+        let mut aig = Aig::from_file("aigs/chain.aag").unwrap();
+        assert_eq!(aig.depth(), 3);
+        aig.balance();
+        assert_eq!(aig.depth(), 2);
+        assert_eq!(aig.num_ands, 3);
+    }
+
+    #[test]
+    fn test_balance_equivalence() {
+        let origin = Aig::from_file("aigs/i10.aag").unwrap();
+        let mut aig = Aig::from_file("aigs/i10.aag").unwrap();
+        aig.balance();
+        assert!(aig.depth() <= origin.depth());
+        assert_eq!(
+            random_output_values(&origin, 0, 64),
+            random_output_values(&aig, 0, 64)
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{
+    cut::{flip_truth, Cut},
+    genlib::Library,
+    Aig, AigEdge, AigLatch, AigNodeId,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+};
+
+const CELL_CUT_SIZE: usize = 5;
+
+const CELL_CUT_LIMIT: usize = 8;
+
+#[derive(Debug, Clone)]
+enum CellChoice {
+    Gate(usize, Vec<AigEdge>),
+    Inverter,
+}
+
+/// A library gate instance, signals are nodes of the mapped aig in a phase.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    gate: usize,
+    inputs: Vec<AigEdge>,
+    output: AigEdge,
+}
+
+impl Cell {
+    pub fn gate(&self) -> usize {
+        self.gate
+    }
+
+    /// the signal driving each pin of the gate.
+    pub fn inputs(&self) -> &[AigEdge] {
+        &self.inputs
+    }
+
+    pub fn output(&self) -> AigEdge {
+        self.output
+    }
+}
+
+/// A gate level netlist, cells are in topological order.
+#[derive(Debug, Clone)]
+pub struct CellNetlist<'a> {
+    library: &'a Library,
+    inputs: Vec<AigNodeId>,
+    latchs: Vec<AigLatch>,
+    outputs: Vec<AigEdge>,
+    bads: Vec<AigEdge>,
+    cells: Vec<Cell>,
+    area: f64,
+    delay: f64,
+}
+
+impl<'a> CellNetlist<'a> {
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    /// the largest sum of pin block delays from an input or latch to an
+    /// output, bad state or latch next.
+    pub fn delay(&self) -> f64 {
+        self.delay
+    }
+
+    fn names(&self) -> HashMap<AigNodeId, String> {
+        let mut names = HashMap::new();
+        for (i, input) in self.inputs.iter().enumerate() {
+            names.insert(*input, format!("i{}", i));
+        }
+        for (i, latch) in self.latchs.iter().enumerate() {
+            names.insert(latch.input, format!("l{}", i));
+        }
+        for cell in &self.cells {
+            names
+                .entry(cell.output.node_id())
+                .or_insert_with(|| format!("n{}", cell.output.node_id()));
+        }
+        names
+    }
+
+    fn signal(names: &HashMap<AigNodeId, String>, signal: AigEdge) -> String {
+        match (signal.node_id(), signal.compl()) {
+            (0, false) => "1'b0".to_string(),
+            (0, true) => "1'b1".to_string(),
+            (id, false) => names[&id].clone(),
+            (id, true) => format!("{}_n", names[&id]),
+        }
+    }
+
+    /// Write the netlist as a structural Verilog module, inputs are named
+    /// `i<k>`, latchs `l<k>` and are clocked by `clk`, outputs `o<k>` and bad
+    /// states `b<k>`.
+    pub fn write_verilog<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let names = self.names();
+        let mut ports = Vec::new();
+        if !self.latchs.is_empty() {
+            ports.push("clk".to_string());
+        }
+        ports.extend((0..self.inputs.len()).map(|i| format!("i{}", i)));
+        ports.extend((0..self.outputs.len()).map(|i| format!("o{}", i)));
+        ports.extend((0..self.bads.len()).map(|i| format!("b{}", i)));
+        writeln!(w, "module aig ({});", ports.join(", "))?;
+        if !self.latchs.is_empty() {
+            writeln!(w, "  input clk;")?;
+        }
+        for i in 0..self.inputs.len() {
+            writeln!(w, "  input i{};", i)?;
+        }
+        for i in 0..self.outputs.len() {
+            writeln!(w, "  output o{};", i)?;
+        }
+        for i in 0..self.bads.len() {
+            writeln!(w, "  output b{};", i)?;
+        }
+        for (i, latch) in self.latchs.iter().enumerate() {
+            writeln!(w, "  reg l{} = 1'b{};", i, latch.init as u8)?;
+        }
+        for cell in &self.cells {
+            writeln!(w, "  wire {};", Self::signal(&names, cell.output))?;
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            let gate = &self.library.gates()[cell.gate];
+            let mut pins: Vec<String> = gate
+                .pins()
+                .iter()
+                .zip(cell.inputs.iter())
+                .map(|(pin, input)| format!(".{}({})", pin, Self::signal(&names, *input)))
+                .collect();
+            pins.push(format!(
+                ".{}({})",
+                gate.output(),
+                Self::signal(&names, cell.output)
+            ));
+            writeln!(w, "  {} g{} ({});", gate.name(), i, pins.join(", "))?;
+        }
+        for (i, out) in self.outputs.iter().enumerate() {
+            writeln!(w, "  assign o{} = {};", i, Self::signal(&names, *out))?;
+        }
+        for (i, bad) in self.bads.iter().enumerate() {
+            writeln!(w, "  assign b{} = {};", i, Self::signal(&names, *bad))?;
+        }
+        if !self.latchs.is_empty() {
+            writeln!(w, "  always @(posedge clk) begin")?;
+            for (i, latch) in self.latchs.iter().enumerate() {
+                writeln!(w, "    l{} <= {};", i, Self::signal(&names, latch.next))?;
+            }
+            writeln!(w, "  end")?;
+        }
+        writeln!(w, "endmodule")
+    }
+}
+
+impl<'a> Display for CellNetlist<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "cell num: {}", self.cells.len())?;
+        writeln!(f, "area: {:.2}", self.area)?;
+        writeln!(f, "delay: {:.2}", self.delay)?;
+        let mut count = vec![0; self.library.gates().len()];
+        for cell in &self.cells {
+            count[cell.gate] += 1;
+        }
+        for (gate, count) in self.library.gates().iter().zip(count) {
+            if count > 0 {
+                writeln!(f, "{}: {}", gate.name(), count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CellMapState<'a> {
+    library: &'a Library,
+    delay: bool,
+    est_refs: Vec<f64>,
+    arrival: Vec<[f64; 2]>,
+    flow: Vec<[f64; 2]>,
+    choice: Vec<[Option<CellChoice>; 2]>,
+}
+
+impl<'a> CellMapState<'a> {
+    fn better(&self, arrival: f64, flow: f64, id: AigNodeId, phase: usize) -> bool {
+        if self.choice[id][phase].is_none() {
+            return true;
+        }
+        let (old_arrival, old_flow) = (self.arrival[id][phase], self.flow[id][phase]);
+        if self.delay {
+            (arrival, flow) < (old_arrival, old_flow)
+        } else {
+            (flow, arrival) < (old_flow, old_arrival)
+        }
+    }
+
+    fn try_inverter(&mut self, id: AigNodeId, phase: usize) {
+        let inverter = &self.library.gates()[self.library.inverter()];
+        let arrival = self.arrival[id][1 - phase] + inverter.delays()[0];
+        let flow = self.flow[id][1 - phase] + inverter.area() / self.est_refs[id];
+        if self.better(arrival, flow, id, phase) {
+            self.arrival[id][phase] = arrival;
+            self.flow[id][phase] = flow;
+            self.choice[id][phase] = Some(CellChoice::Inverter);
+        }
+    }
+
+    fn match_cut(&mut self, id: AigNodeId, cut: &Cut) {
+        let n = cut.len();
+        for mask in 0..1usize << n {
+            let mut truth = cut.truth();
+            for var in 0..n {
+                if mask >> var & 1 == 1 {
+                    truth = flip_truth(truth, var);
+                }
+            }
+            for phase in 0..2 {
+                let target = if phase == 1 { !truth } else { truth };
+                let (gate, perm) = match self.library.find(n, target) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let gate_info = &self.library.gates()[gate];
+                let inputs: Vec<AigEdge> = perm
+                    .iter()
+                    .map(|var| AigEdge::new(cut.leaves()[*var], mask >> var & 1 == 1))
+                    .collect();
+                let mut arrival: f64 = 0.0;
+                let mut flow = gate_info.area();
+                for (input, delay) in inputs.iter().zip(gate_info.delays()) {
+                    let p = input.compl() as usize;
+                    arrival = arrival.max(self.arrival[input.node_id()][p] + delay);
+                    flow += self.flow[input.node_id()][p];
+                }
+                flow /= self.est_refs[id];
+                if self.better(arrival, flow, id, phase) {
+                    self.arrival[id][phase] = arrival;
+                    self.flow[id][phase] = flow;
+                    self.choice[id][phase] = Some(CellChoice::Gate(gate, inputs));
+                }
+            }
+        }
+    }
+}
+
+impl Aig {
+    /// Cover the aig with gates of the library by matching the truth tables
+    /// of its cuts under every input and output phase, choosing by area flow
+    /// or by delay first when `delay` is set.
+    pub fn cell_map<'a>(&self, library: &'a Library, delay: bool) -> CellNetlist<'a> {
+        let num_nodes = self.num_nodes();
+        let cuts = self.enumerate_cuts(CELL_CUT_SIZE, CELL_CUT_LIMIT);
+        let mut state = CellMapState {
+            library,
+            delay,
+            est_refs: self
+                .nodes
+                .iter()
+                .map(|n| n.fanouts.len().max(1) as f64)
+                .collect(),
+            arrival: vec![[0.0; 2]; num_nodes],
+            flow: vec![[0.0; 2]; num_nodes],
+            choice: vec![[None, None]; num_nodes],
+        };
+        for id in self.nodes_range() {
+            if self.nodes[id].is_and() {
+                for cut in &cuts[id][1..] {
+                    state.match_cut(id, cut);
+                }
+                for phase in 0..2 {
+                    if let Some(CellChoice::Gate(..)) = state.choice[id][1 - phase] {
+                        state.try_inverter(id, phase);
+                    }
+                }
+                assert!(
+                    state.choice[id].iter().all(|c| c.is_some()),
+                    "the library can not implement node {}",
+                    id
+                );
+            } else {
+                state.try_inverter(id, 1);
+            }
+        }
+        let roots = self.roots();
+        let mut used = vec![[false; 2]; num_nodes];
+        let mut stack: Vec<AigEdge> = roots.clone();
+        while let Some(signal) = stack.pop() {
+            let (id, phase) = (signal.node_id(), signal.compl() as usize);
+            if used[id][phase] {
+                continue;
+            }
+            used[id][phase] = true;
+            match &state.choice[id][phase] {
+                Some(CellChoice::Gate(_, inputs)) => stack.extend(inputs),
+                Some(CellChoice::Inverter) => stack.push(AigEdge::new(id, phase == 0)),
+                None => (),
+            }
+        }
+        let mut cells = Vec::new();
+        for id in self.nodes_range() {
+            let mut phases: Vec<usize> = (0..2)
+                .filter(|p| used[id][*p] && state.choice[id][*p].is_some())
+                .collect();
+            phases.sort_by_key(|p| matches!(state.choice[id][*p], Some(CellChoice::Inverter)));
+            for phase in phases {
+                let (gate, inputs) = match state.choice[id][phase].as_ref().unwrap() {
+                    CellChoice::Gate(gate, inputs) => (*gate, inputs.clone()),
+                    CellChoice::Inverter => {
+                        (library.inverter(), vec![AigEdge::new(id, phase == 0)])
+                    }
+                };
+                cells.push(Cell {
+                    gate,
+                    inputs,
+                    output: AigEdge::new(id, phase == 1),
+                });
+            }
+        }
+        let area = cells.iter().map(|c| library.gates()[c.gate].area()).sum();
+        let delay = roots
+            .iter()
+            .map(|r| state.arrival[r.node_id()][r.compl() as usize])
+            .fold(0.0, f64::max);
+        CellNetlist {
+            library,
+            inputs: self.inputs.clone(),
+            latchs: self.latchs.clone(),
+            outputs: self.outputs.clone(),
+            bads: self.bads.clone(),
+            cells,
+            area,
+            delay,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{genlib::Library, Aig, AigEdge};
+
+    #[test]
+    fn test_cell_map() {
+        let library = Library::from_file("aigs/simple.genlib").unwrap();
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let netlist = aig.cell_map(&library, false);
+        assert!(netlist.area() > 0.0);
+        for minterm in 0..8 {
+            let assigns: Vec<AigEdge> = (0..3)
+                .map(|i| AigEdge::new(aig.inputs[i], minterm >> i & 1 == 0))
+                .collect();
+            let expect = aig.evaluate(&assigns);
+            let mut value = vec![[false, true]; aig.num_nodes()];
+            for a in &assigns {
+                value[a.node_id()][0] = !a.compl();
+            }
+            for cell in netlist.cells() {
+                let mut index = 0;
+                for (i, input) in cell.inputs().iter().enumerate() {
+                    index |= (value[input.node_id()][input.compl() as usize] as usize) << i;
+                }
+                let gate = &library.gates()[cell.gate()];
+                value[cell.output().node_id()][cell.output().compl() as usize] =
+                    gate.truth() >> index & 1 == 1;
+            }
+            for out in &aig.outputs {
+                let v = value[out.node_id()][out.compl() as usize];
+                assert_eq!(v, expect[out.node_id()].unwrap() ^ out.compl());
+            }
+        }
+        let mut verilog = Vec::new();
+        netlist.write_verilog(&mut verilog).unwrap();
+        let verilog = String::from_utf8(verilog).unwrap();
+        assert!(verilog.starts_with("module aig (i0, i1, i2, o0, o1);"));
+        assert!(verilog.ends_with("endmodule\n"));
+    }
+}
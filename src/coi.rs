@@ -0,0 +1,71 @@
+use crate::{aiger::AigSymbols, Aig, AigEdge};
+
+impl Aig {
+    /// Sequential cone of influence of the given bad states, following latch
+    /// next functions until a fixpoint.
+    pub fn sequential_logic_cone(&self, bads: &[usize]) -> Vec<bool> {
+        let mut roots: Vec<AigEdge> = bads.iter().map(|b| self.bads[*b]).collect();
+        let mut taken = vec![false; self.latchs.len()];
+        loop {
+            let cone = self.fanin_logic_cone(&roots);
+            let mut changed = false;
+            for (i, latch) in self.latchs.iter().enumerate() {
+                if cone[latch.input] && !taken[i] {
+                    taken[i] = true;
+                    roots.push(latch.next);
+                    changed = true;
+                }
+            }
+            if !changed {
+                return cone;
+            }
+        }
+    }
+
+    /// Keep only the given bad states and the inputs and latchs in their
+    /// sequential cone of influence, outputs are dropped. Returns the original
+    /// index of every kept input and latch, so traces on the reduced design
+    /// can be lifted back. The FRAIG state is dropped.
+    pub fn coi_reduce(&mut self, bads: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let cone = self.sequential_logic_cone(bads);
+        let input_map: Vec<usize> = (0..self.inputs.len())
+            .filter(|i| cone[self.inputs[*i]])
+            .collect();
+        let latch_map: Vec<usize> = (0..self.latchs.len())
+            .filter(|i| cone[self.latchs[*i].input])
+            .collect();
+        self.inputs = input_map.iter().map(|i| self.inputs[*i]).collect();
+        self.latchs = latch_map.iter().map(|i| self.latchs[*i].clone()).collect();
+        self.bads = bads.iter().map(|b| self.bads[*b]).collect();
+        self.outputs.clear();
+        AigSymbols::select(&mut self.symbols.inputs, &input_map);
+        AigSymbols::select(&mut self.symbols.latchs, &latch_map);
+        AigSymbols::select(&mut self.symbols.bads, bads);
+        self.symbols.outputs.clear();
+        self.rebuild(&[]);
+        (input_map, latch_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Aig;
+
+    #[test]
+    fn test_coi_reduce() {
+        let mut aig = Aig::from_file("aigs/coi.aag").unwrap();
+        let (inputs, latchs) = aig.coi_reduce(&[0]);
+        assert_eq!(inputs, [0]);
+        assert_eq!(latchs, [0, 1]);
+        assert_eq!(aig.bads.len(), 1);
+        assert_eq!(aig.num_ands, 0);
+        assert_eq!(aig.num_nodes(), 4);
+        let symbols = aig.symbols();
+        assert_eq!(symbols.input(0), Some("a"));
+        assert_eq!(symbols.input(1), None);
+        assert_eq!(symbols.latch(1), Some("q"));
+        assert_eq!(symbols.latch(2), None);
+        assert_eq!(symbols.bad(0), Some("bad_q"));
+        assert_eq!(symbols.bad(1), None);
+    }
+}
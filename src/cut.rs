@@ -0,0 +1,166 @@
+use crate::{Aig, AigNodeId};
+
+pub type TruthTable = u64;
+
+pub const TRUTH_MAX_VARS: usize = 6;
+
+pub const TRUTH_VARS: [TruthTable; TRUTH_MAX_VARS] = [
+    0xAAAAAAAAAAAAAAAA,
+    0xCCCCCCCCCCCCCCCC,
+    0xF0F0F0F0F0F0F0F0,
+    0xFF00FF00FF00FF00,
+    0xFFFF0000FFFF0000,
+    0xFFFFFFFF00000000,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cut {
+    leaves: Vec<AigNodeId>,
+    truth: TruthTable,
+}
+
+impl Cut {
+    pub(crate) fn trivial(node: AigNodeId) -> Self {
+        Self {
+            leaves: vec![node],
+            truth: TRUTH_VARS[0],
+        }
+    }
+
+    pub fn leaves(&self) -> &[AigNodeId] {
+        &self.leaves
+    }
+
+    /// truth table over the leaves, leaf i is variable i. only valid for cuts
+    /// with at most `TRUTH_MAX_VARS` leaves.
+    pub fn truth(&self) -> TruthTable {
+        self.truth
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub(crate) fn is_subset_of(&self, leaves: &[AigNodeId]) -> bool {
+        self.leaves.iter().all(|l| leaves.binary_search(l).is_ok())
+    }
+
+    fn merge(&self, other: &Cut, k: usize) -> Option<Vec<AigNodeId>> {
+        let mut leaves = Vec::with_capacity(k);
+        let (mut i, mut j) = (0, 0);
+        while i < self.leaves.len() || j < other.leaves.len() {
+            let next = if j == other.leaves.len()
+                || (i < self.leaves.len() && self.leaves[i] < other.leaves[j])
+            {
+                i += 1;
+                self.leaves[i - 1]
+            } else if i == self.leaves.len() || other.leaves[j] < self.leaves[i] {
+                j += 1;
+                other.leaves[j - 1]
+            } else {
+                i += 1;
+                j += 1;
+                self.leaves[i - 1]
+            };
+            if leaves.len() == k {
+                return None;
+            }
+            leaves.push(next);
+        }
+        Some(leaves)
+    }
+
+    /// the and of the two cuts under the fanin complements, none if it has
+    /// more than k leaves. the truth table is only computed when k is at
+    /// most `TRUTH_MAX_VARS`.
+    pub(crate) fn and(c0: &Cut, compl0: bool, c1: &Cut, compl1: bool, k: usize) -> Option<Cut> {
+        let leaves = c0.merge(c1, k)?;
+        let truth = if k <= TRUTH_MAX_VARS {
+            let mut t0 = stretch_truth(c0.truth, &c0.leaves, &leaves);
+            let mut t1 = stretch_truth(c1.truth, &c1.leaves, &leaves);
+            if compl0 {
+                t0 = !t0;
+            }
+            if compl1 {
+                t1 = !t1;
+            }
+            t0 & t1
+        } else {
+            0
+        };
+        Some(Cut { leaves, truth })
+    }
+}
+
+pub fn stretch_truth(truth: TruthTable, from: &[AigNodeId], to: &[AigNodeId]) -> TruthTable {
+    let pos: Vec<usize> = from.iter().map(|l| to.binary_search(l).unwrap()).collect();
+    let mut ret = 0;
+    for minterm in 0..(1 << TRUTH_MAX_VARS) {
+        let mut src = 0;
+        for (i, p) in pos.iter().enumerate() {
+            if minterm >> p & 1 == 1 {
+                src |= 1 << i;
+            }
+        }
+        if truth >> src & 1 == 1 {
+            ret |= 1 << minterm;
+        }
+    }
+    ret
+}
+
+/// complement variable `var` of the truth table.
+pub fn flip_truth(truth: TruthTable, var: usize) -> TruthTable {
+    let shift = 1 << var;
+    ((truth & TRUTH_VARS[var]) >> shift) | ((truth & !TRUTH_VARS[var]) << shift)
+}
+
+impl Aig {
+    /// enumerate at most `limit` k-feasible cuts for every node, the trivial
+    /// cut of a node is always the first one.
+    pub fn enumerate_cuts(&self, k: usize, limit: usize) -> Vec<Vec<Cut>> {
+        let mut cuts: Vec<Vec<Cut>> = Vec::with_capacity(self.num_nodes());
+        for node in self.nodes.iter() {
+            let mut node_cuts: Vec<Cut> = Vec::new();
+            if node.is_and() {
+                let fanin0 = node.fanin0();
+                let fanin1 = node.fanin1();
+                for c0 in &cuts[fanin0.node_id()] {
+                    for c1 in &cuts[fanin1.node_id()] {
+                        let cut = match Cut::and(c0, fanin0.compl(), c1, fanin1.compl(), k) {
+                            Some(cut) => cut,
+                            None => continue,
+                        };
+                        if node_cuts.iter().any(|c| c.is_subset_of(&cut.leaves)) {
+                            continue;
+                        }
+                        node_cuts.retain(|c| !cut.is_subset_of(&c.leaves));
+                        node_cuts.push(cut);
+                    }
+                }
+                node_cuts.sort_by_key(|c| c.len());
+                node_cuts.truncate(limit);
+            }
+            node_cuts.insert(0, Cut::trivial(node.node_id()));
+            cuts.push(node_cuts);
+        }
+        cuts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Aig;
+
+    #[test]
+    fn test_cuts() {
+        let aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let cuts = aig.enumerate_cuts(4, 8);
+        assert!(cuts[5]
+            .iter()
+            .any(|c| c.leaves() == [1, 2] && c.truth() as u16 == 0xCCCC));
+        assert!(cuts[7]
+            .iter()
+            .any(|c| c.leaves() == [2, 3] && c.truth() as u16 == 0x5555));
+    }
+}
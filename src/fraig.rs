@@ -1,6 +1,9 @@
 use crate::{
     sat::SatSolver,
-    simulate::{Simulation, SimulationWord, SimulationWords, SimulationWordsHash},
+    simulate::{
+        RandomWordGenerator, SimdSimulationWord, Simulation, SimulationWord, SimulationWords,
+        SimulationWordsHash,
+    },
     symbolic_mc::{
         TOTAL_ADD_PATTERN, TOTAL_FE_MERGE_NODE, TOTAL_FRAIG_ADD_SAT,
         TOTAL_FRAIG_LAZY_CHECKED_WITHOUT_SAT, TOTAL_RESIM, TOTAL_SIMAND, TOTAL_SIMAND_NOSAT_INSERT,
@@ -8,19 +11,74 @@ use crate::{
     },
     Aig, AigEdge, AigNode, AigNodeId,
 };
-use rand::{thread_rng, Rng};
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     mem::{replace, take},
     vec,
 };
 
-#[derive(Debug)]
+/// distance-1 neighbours of a counterexample tried to split its class.
+const FRAIG_DISTANCE1_FLIPS: usize = 16;
+
+/// distance-1 neighbours of a counterexample of the lazy path, each takes a
+/// lazy bit, so far fewer are added.
+const FRAIG_LAZY_FLIPS: usize = 2;
+
+/// the indices of at most `limit` words, the newest words that still refine
+/// the classes of the representatives first, padded with the newest other
+/// words up to a simd word.
+fn useful_words(simulation: &Simulation, reps: &[AigEdge], limit: usize) -> Vec<usize> {
+    let nword = simulation.nword();
+    let mut blocks = vec![0; reps.len()];
+    let mut nblock = 1;
+    let mut keep = Vec::new();
+    for i in (0..nword).rev() {
+        if keep.len() == limit {
+            break;
+        }
+        let mut refine: HashMap<(usize, SimulationWord), usize> = HashMap::new();
+        let refined: Vec<usize> = reps
+            .iter()
+            .zip(blocks.iter())
+            .map(|(rep, block)| {
+                let word = simulation[rep.node_id()][i];
+                let word = if rep.compl() { !word } else { word };
+                let next = refine.len();
+                *refine.entry((*block, word)).or_insert(next)
+            })
+            .collect();
+        if refine.len() > nblock {
+            nblock = refine.len();
+            blocks = refined;
+            keep.push(i);
+        }
+    }
+    let mut kept = vec![false; nword];
+    for i in &keep {
+        kept[*i] = true;
+    }
+    for i in (0..nword).rev() {
+        if keep.len() >= SimdSimulationWord::LANES {
+            break;
+        }
+        if !kept[i] {
+            keep.push(i);
+        }
+    }
+    keep.sort_unstable();
+    keep
+}
+
+#[derive(Debug, Clone)]
 pub struct FrAig {
     simulation: Simulation,
     sim_map: HashMap<SimulationWordsHash, Vec<AigEdge>>,
     lazy_cex: Vec<SimulationWord>,
     ncex: usize,
+    rng: RandomWordGenerator,
+    word_limit: Option<usize>,
+    /// edges assumed to hold in every SAT query and pattern.
+    constraints: Vec<AigEdge>,
 }
 
 impl FrAig {
@@ -47,48 +105,125 @@ impl FrAig {
             for rep_lazy in rep_lazys.iter() {
                 let (hash_value, compl) = self.simulation.abs_hash_value(*rep_lazy);
                 assert!(!compl);
-                if let Some(_) = self.sim_map.insert(hash_value, vec![*rep_lazy]) {
-                    panic!()
-                }
+                self.sim_map.entry(hash_value).or_default().push(*rep_lazy);
             }
         }
+        self.limit_words();
     }
 
-    fn lazy_resimulate(&mut self, nodes: &[AigNode]) {
-        for node in nodes.iter().skip(1) {
-            if node.is_and() {
-                let fanin0 = node.fanin0();
-                let fanin1 = node.fanin1();
-                let v0 = if fanin0.compl() {
-                    !self.lazy_cex[fanin0.node_id()]
-                } else {
-                    self.lazy_cex[fanin0.node_id()]
-                };
-                let v1 = if fanin1.compl() {
-                    !self.lazy_cex[fanin1.node_id()]
-                } else {
-                    self.lazy_cex[fanin1.node_id()]
-                };
-                self.lazy_cex[node.node_id()] = v0 & v1;
+    /// drop the words not worth keeping once the word limit is exceeded and
+    /// rebuild `sim_map` from the remaining words. The words are cut down to
+    /// half the limit but at least a simd word, so the next cut waits for
+    /// many new words.
+    fn limit_words(&mut self) {
+        let limit = match self.word_limit {
+            Some(limit) if self.simulation.nword() > limit => limit,
+            _ => return,
+        };
+        let reps: Vec<AigEdge> = self.sim_map.values().flatten().copied().collect();
+        let target = (limit / 2).max(SimdSimulationWord::LANES);
+        let keep = useful_words(&self.simulation, &reps, target);
+        self.simulation.retain_words(&keep);
+        let old_map = take(&mut self.sim_map);
+        for rep in old_map.into_values().flatten() {
+            let sim = &self.simulation[rep.node_id()];
+            self.sim_map
+                .entry(sim.abs_hash_value())
+                .or_default()
+                .push(AigEdge::new(rep.node_id(), sim.compl()));
+        }
+    }
+
+    /// resimulate the lazy words in the transitive fanout of the changed
+    /// nodes only, in topological order, stopping where values do not change.
+    fn lazy_resimulate(&mut self, nodes: &[AigNode], changed: &[AigNodeId]) {
+        let mut queue = BTreeSet::new();
+        for id in changed {
+            if nodes[*id].is_and() {
+                queue.insert(*id);
+            }
+            queue.extend(nodes[*id].fanouts.iter().map(|f| f.node_id()));
+        }
+        while let Some(id) = queue.pop_first() {
+            let node = &nodes[id];
+            let fanin0 = node.fanin0();
+            let fanin1 = node.fanin1();
+            let v0 = if fanin0.compl() {
+                !self.lazy_cex[fanin0.node_id()]
+            } else {
+                self.lazy_cex[fanin0.node_id()]
+            };
+            let v1 = if fanin1.compl() {
+                !self.lazy_cex[fanin1.node_id()]
+            } else {
+                self.lazy_cex[fanin1.node_id()]
+            };
+            if self.lazy_cex[id] != v0 & v1 {
+                self.lazy_cex[id] = v0 & v1;
+                queue.extend(node.fanouts.iter().map(|f| f.node_id()));
             }
         }
     }
 
-    fn add_pattern(&mut self, nodes: &[AigNode], pattern: &[AigEdge]) {
-        unsafe { TOTAL_ADD_PATTERN += 1 };
+    /// write the pattern into the current lazy bit, the other bits are kept.
+    fn set_pattern(&mut self, nodes: &[AigNode], pattern: &[AigEdge]) {
+        let mut changed = Vec::new();
         for e in pattern {
+            let old = self.lazy_cex[e.node_id()];
             if e.compl() {
                 self.lazy_cex[e.node_id()] &= !(1 << self.ncex);
             } else {
                 self.lazy_cex[e.node_id()] |= 1 << self.ncex;
             }
+            if self.lazy_cex[e.node_id()] != old {
+                changed.push(e.node_id());
+            }
         }
-        self.lazy_resimulate(nodes);
+        self.lazy_resimulate(nodes, &changed);
+    }
+
+    fn add_pattern(&mut self, nodes: &[AigNode], pattern: &[AigEdge]) {
+        self.set_pattern(nodes, pattern);
+        self.next_pattern();
+    }
+
+    /// keep the pattern in the current lazy bit and move to the next one.
+    fn next_pattern(&mut self) {
+        unsafe { TOTAL_ADD_PATTERN += 1 };
         self.ncex += 1;
         if self.ncex == SimulationWord::BITS as usize {
             self.submit_lazy();
         }
     }
+
+    /// add a SAT counterexample and its distance-1 neighbours on the first
+    /// inputs of the model, the neighbours violating a constraint are undone.
+    fn add_cex(&mut self, nodes: &[AigNode], cex: &[AigEdge]) {
+        self.add_pattern(nodes, cex);
+        let flips: Vec<AigEdge> = cex
+            .iter()
+            .copied()
+            .filter(|e| nodes[e.node_id()].is_cinput())
+            .take(FRAIG_LAZY_FLIPS)
+            .collect();
+        for input in flips {
+            let flipped: Vec<AigEdge> = cex
+                .iter()
+                .map(|e| if *e == input { !*e } else { *e })
+                .collect();
+            self.set_pattern(nodes, &flipped);
+            let bit = self.ncex;
+            let satisfied = self
+                .constraints
+                .iter()
+                .all(|c| (self.lazy_cex[c.node_id()] >> bit & 1 == 1) ^ c.compl());
+            if satisfied {
+                self.next_pattern();
+            } else {
+                self.set_pattern(nodes, &[input]);
+            }
+        }
+    }
 }
 
 impl FrAig {
@@ -99,9 +234,9 @@ impl FrAig {
     pub fn new_input_node(&mut self, node: AigNodeId) {
         assert_eq!(self.simulation.num_nodes(), node);
         assert_eq!(self.lazy_cex.len(), node);
-        let mut sim = SimulationWords::new(self.simulation.nword());
+        let mut sim = SimulationWords::new(self.simulation.nword(), &mut self.rng);
         while self.sim_map.contains_key(&sim.abs_hash_value()) {
-            sim = SimulationWords::new(self.simulation.nword());
+            sim = SimulationWords::new(self.simulation.nword(), &mut self.rng);
         }
         let edge = AigEdge::new(node, sim.compl());
         assert!(self
@@ -109,8 +244,8 @@ impl FrAig {
             .insert(sim.abs_hash_value(), vec![edge])
             .is_none());
         self.simulation.add_node(sim);
-        let mut rng = thread_rng();
-        self.lazy_cex.push(rng.gen());
+        let word = self.rng.rand_word();
+        self.lazy_cex.push(word);
     }
 
     #[inline]
@@ -139,6 +274,9 @@ impl FrAig {
             Some(cans) => {
                 let cans = cans.clone();
                 for can in cans {
+                    if !sim.abs_equal(&self.simulation[can.node_id()]) {
+                        continue;
+                    }
                     let can = if sim.compl() { !can } else { can };
                     if lazy_value_closure(can, &self.lazy_cex)
                         != new_and_lazy_closure(&self.lazy_cex)
@@ -151,8 +289,13 @@ impl FrAig {
                         continue;
                     }
                     unsafe { TOTAL_FRAIG_ADD_SAT += 1 };
-                    match solver.equivalence_check_xy_z(fanin0, fanin1, can) {
-                        Some(s) => self.add_pattern(nodes, s),
+                    match solver.constrained_equivalence_check_xy_z(
+                        fanin0,
+                        fanin1,
+                        can,
+                        &self.constraints,
+                    ) {
+                        Some(s) => self.add_cex(nodes, s),
                         None => {
                             return Some(can);
                         }
@@ -194,8 +337,7 @@ impl FrAig {
 }
 
 impl FrAig {
-    fn gen_pattern(nodes: &[AigNode], s: &[AigEdge]) -> Vec<bool> {
-        let mut r = thread_rng();
+    fn gen_pattern(nodes: &[AigNode], s: &[AigEdge], rng: &mut RandomWordGenerator) -> Vec<bool> {
         let mut flags = vec![false; nodes.len()];
         let mut ret = vec![false; nodes.len()];
         ret[0] = true;
@@ -213,13 +355,29 @@ impl FrAig {
                     let v1 = ret[fanin1.node_id()] ^ fanin1.compl();
                     ret[i] = v0 & v1;
                 } else {
-                    ret[i] = r.gen();
+                    ret[i] = rng.rand_bool();
                 }
             }
         }
         ret
     }
 
+    /// the pattern with the value of one input or latch input flipped and
+    /// its fanout resimulated.
+    fn flip_pattern(nodes: &[AigNode], pattern: &[bool], input: AigNodeId) -> Vec<bool> {
+        let mut ret = pattern.to_vec();
+        ret[input] = !ret[input];
+        for i in input + 1..nodes.len() {
+            if nodes[i].is_and() {
+                let fanin0 = nodes[i].fanin0();
+                let fanin1 = nodes[i].fanin1();
+                ret[i] = (ret[fanin0.node_id()] ^ fanin0.compl())
+                    & (ret[fanin1.node_id()] ^ fanin1.compl());
+            }
+        }
+        ret
+    }
+
     pub fn cleanup_redundant(
         &mut self,
         node_map: &[Option<AigNodeId>],
@@ -243,6 +401,9 @@ impl FrAig {
         for should in should_remove {
             assert!(self.sim_map.remove(&should).is_some());
         }
+        for c in &mut self.constraints {
+            c.set_nodeid(node_map[c.node_id()].unwrap());
+        }
         let old = take(&mut self.lazy_cex);
         for (id, old_sim) in old.into_iter().enumerate() {
             if let Some(dst) = node_map[id] {
@@ -305,54 +466,199 @@ impl FrAig {
     }
 }
 
+impl FrAig {
+    /// renumber the nodes, node i of the result is node `order[i]` and
+    /// `node_map` is the inverse.
+    pub fn reorder(&mut self, order: &[AigNodeId], node_map: &[Option<AigNodeId>]) {
+        self.simulation.reorder(order);
+        for cans in self.sim_map.values_mut() {
+            for can in cans.iter_mut() {
+                can.set_nodeid(node_map[can.node_id()].unwrap());
+            }
+        }
+        let old = take(&mut self.lazy_cex);
+        self.lazy_cex = order.iter().map(|id| old[*id]).collect();
+        for c in &mut self.constraints {
+            c.set_nodeid(node_map[c.node_id()].unwrap());
+        }
+    }
+
+    pub fn constraints(&self) -> &[AigEdge] {
+        &self.constraints
+    }
+}
+
 impl Aig {
+    /// Bound the simulation words kept per node by the FRAIG state. Once
+    /// counterexamples grow the words past the limit, the words that no
+    /// longer split any class are dropped, newer ones are preferred. `None`
+    /// keeps every word. The limit is kept on the design, so it applies to
+    /// the current FRAIG state and to every later `fraig`.
+    pub fn set_simulation_word_limit(&mut self, limit: Option<usize>) {
+        if let Some(limit) = limit {
+            assert!(limit >= SimdSimulationWord::LANES);
+        }
+        self.simulation_options.word_limit = limit;
+        if let Some(fraig) = self.fraig.as_mut() {
+            fraig.word_limit = limit;
+            fraig.limit_words();
+        }
+    }
+
+    pub fn simulation_word_limit(&self) -> Option<usize> {
+        self.simulation_options.word_limit
+    }
+
+    /// the candidate classes of nodes with equal simulation words up to
+    /// complement, the classes sharing a hash are kept apart.
     fn get_candidate(
         &mut self,
         simulation: &Simulation,
-    ) -> HashMap<SimulationWordsHash, Vec<AigEdge>> {
-        let mut candidate_map: HashMap<SimulationWordsHash, Vec<AigEdge>> = HashMap::new();
+    ) -> HashMap<SimulationWordsHash, Vec<Vec<AigEdge>>> {
+        let mut candidate_map: HashMap<SimulationWordsHash, Vec<Vec<AigEdge>>> = HashMap::new();
         for idx in self.nodes_range_with_true() {
             let edge = AigEdge::new(idx, simulation[idx].compl());
-            match candidate_map.get_mut(&simulation.abs_hash_value(edge).0) {
+            let classes = candidate_map
+                .entry(simulation.abs_hash_value(edge).0)
+                .or_default();
+            match classes
+                .iter_mut()
+                .find(|c| simulation[c[0].node_id()].abs_equal(&simulation[idx]))
+            {
                 Some(candidate) => candidate.push(edge),
-                None => {
-                    assert!(candidate_map
-                        .insert(simulation[idx].abs_hash_value(), vec![edge],)
-                        .is_none());
-                }
+                None => classes.push(vec![edge]),
             }
         }
         candidate_map
     }
 
     pub fn fraig(&mut self) {
+        self.fraig_with_constraints(&[]);
+    }
+
+    /// FRAIG where only the assignments satisfying every constraint edge
+    /// count, the patterns and SAT counterexamples satisfy them. Nodes equal
+    /// whenever the constraints hold are merged, so the result is equivalent
+    /// to the design only under the constraints. The cones of the constraints
+    /// are kept unmerged so the constraints stay intact.
+    pub fn fraig_with_constraints(&mut self, constraints: &[AigEdge]) {
+        self.fraig_refine(constraints, true);
+    }
+
+    /// the FRAIG refinement behind `fraig_with_constraints`, the classes are
+    /// checked by one SAT call for all unsplit members if `class_query`, else
+    /// member by member. Returns the number of SAT calls.
+    fn fraig_refine(&mut self, constraints: &[AigEdge], class_query: bool) -> usize {
         assert!(self.fraig.is_none());
-        let mut simulation = self.new_simulation(1);
+        let constraints: Vec<AigEdge> = constraints
+            .iter()
+            .copied()
+            .filter(|c| *c != AigEdge::constant_edge(true))
+            .collect();
+        let mut simulation = if constraints.is_empty() {
+            self.new_simulation(1)
+        } else {
+            self.new_constrained_simulation(1, &constraints)
+        };
+        let protected = self.fanin_logic_cone(&constraints);
+        let mut rng = self.new_word_generator();
+        let num_nodes = self.num_nodes();
+        let mut nsat = 0;
         loop {
             let candidates = self.get_candidate(&simulation);
             // dbg!(candidates.keys().count());
             let mut update = false;
             let mut patterns = Vec::new();
-            for candidate in candidates.values() {
+            let mut classes: Vec<&Vec<AigEdge>> = candidates.values().flatten().collect();
+            classes.sort_by_key(|c| c[0].node_id());
+            for candidate in classes {
                 if candidate.len() == 1 {
                     continue;
                 }
-                for c in &candidate[1..] {
-                    if let Some(s) = self.sat_solver.equivalence_check(candidate[0], *c) {
-                        patterns.push(FrAig::gen_pattern(&self.nodes, s));
-                        update = true;
+                // every pattern is checked on the whole class, a member split
+                // from the representative by an earlier counterexample or its
+                // distance-1 neighbours needs no SAT call in this round. The
+                // members left are checked at once, by asking for one of them
+                // to differ from the representative.
+                let rep = candidate[0];
+                let split = |p: &[bool], c: AigEdge| {
+                    p[rep.node_id()] ^ rep.compl() != p[c.node_id()] ^ c.compl()
+                };
+                let satisfied = |p: &[bool]| constraints.iter().all(|c| p[c.node_id()] ^ c.compl());
+                let mut class_patterns: Vec<Vec<bool>> = Vec::new();
+                let mut proved = Vec::new();
+                loop {
+                    let unsplit: Vec<AigEdge> = candidate[1..]
+                        .iter()
+                        .copied()
+                        .filter(|c| !proved.contains(c))
+                        .filter(|c| !class_patterns.iter().any(|p| split(p, *c)))
+                        .collect();
+                    if unsplit.is_empty() {
+                        break;
                     }
+                    nsat += 1;
+                    let single = unsplit.len() == 1 || !class_query;
+                    let s = if single {
+                        self.sat_solver
+                            .constrained_equivalence_check(rep, unsplit[0], &constraints)
+                            .map(|s| s.to_vec())
+                    } else {
+                        let equals: Vec<AigEdge> = unsplit
+                            .iter()
+                            .map(|c| self.new_equal_node(rep, *c))
+                            .collect();
+                        let mut assumptions = constraints.clone();
+                        assumptions.push(!self.new_and_nodes(equals));
+                        self.sat_solver.solve(&assumptions).map(|s| s.to_vec())
+                    };
+                    let Some(mut s) = s else {
+                        if single {
+                            proved.push(unsplit[0]);
+                            continue;
+                        }
+                        break;
+                    };
+                    s.retain(|e| e.node_id() < num_nodes);
+                    let nodes = &self.nodes[..num_nodes];
+                    let flips: Vec<AigNodeId> = s
+                        .iter()
+                        .map(|e| e.node_id())
+                        .filter(|id| nodes[*id].is_cinput())
+                        .take(FRAIG_DISTANCE1_FLIPS)
+                        .collect();
+                    class_patterns.push(FrAig::gen_pattern(nodes, &s, &mut rng));
+                    let base = class_patterns.len() - 1;
+                    for input in flips {
+                        let flipped = FrAig::flip_pattern(nodes, &class_patterns[base], input);
+                        if satisfied(&flipped)
+                            && unsplit.iter().any(|m| {
+                                split(&flipped, *m) && !class_patterns.iter().any(|p| split(p, *m))
+                            })
+                        {
+                            class_patterns.push(flipped);
+                        }
+                    }
+                    update = true;
                 }
+                patterns.extend(class_patterns);
             }
+            self.truncate_nodes(num_nodes);
             if !update {
-                let mut sim_map = HashMap::new();
+                let mut sim_map: HashMap<SimulationWordsHash, Vec<AigEdge>> = HashMap::new();
                 let mut should_merge = Vec::new();
-                for (k, candidate) in &candidates {
-                    assert_eq!(*k, simulation.abs_hash_value(candidate[0]).0);
-                    assert!(sim_map.insert(*k, vec![candidate[0]]).is_none());
-                    for c in &candidate[1..] {
-                        assert_eq!(*k, simulation.abs_hash_value(*c).0);
-                        should_merge.push((*c, candidate[0]));
+                for (k, classes) in &candidates {
+                    for candidate in classes {
+                        assert_eq!(*k, simulation.abs_hash_value(candidate[0]).0);
+                        sim_map.entry(*k).or_default().push(candidate[0]);
+                        for c in &candidate[1..] {
+                            assert_eq!(*k, simulation.abs_hash_value(*c).0);
+                            if protected[c.node_id()] {
+                                sim_map.entry(*k).or_default().push(*c);
+                            } else {
+                                should_merge.push((*c, candidate[0]));
+                            }
+                        }
                     }
                 }
                 should_merge.sort_by(|x, y| x.0.cmp(&y.0));
@@ -365,26 +671,49 @@ impl Aig {
                     sim_map,
                     lazy_cex: Vec::new(),
                     ncex: 0,
+                    rng,
+                    word_limit: self.simulation_options.word_limit,
+                    constraints,
                 });
-                self.fraig.as_mut().unwrap().lazy_cex =
-                    self.fraig.as_ref().unwrap().default_lazy_cexs();
+                let fraig = self.fraig.as_mut().unwrap();
+                fraig.limit_words();
+                fraig.lazy_cex = fraig.default_lazy_cexs();
                 self.cleanup_redundant(&mut []);
-                return;
+                unsafe { TOTAL_FRAIG_ADD_SAT += nsat };
+                return nsat;
             } else {
                 assert!(self.num_nodes() == patterns[0].len());
+                // the spare bits of the last word repeat the first patterns,
+                // so every bit of the words is a real assignment.
+                let nbit = SimulationWord::BITS as usize;
                 let mut words = vec![0; self.num_nodes()];
-                for (bit, pattern) in patterns.into_iter().enumerate() {
-                    if bit > 0 && bit % (SimulationWord::BITS as usize) == 0 {
+                for bit in 0..patterns.len().next_multiple_of(nbit) {
+                    if bit > 0 && bit % nbit == 0 {
                         let submit = replace(&mut words, vec![0; self.num_nodes()]);
                         simulation.add_words(submit);
                     }
-                    for (idx, p) in pattern.into_iter().enumerate() {
-                        if p {
-                            words[idx] |= 1 << bit;
+                    for (idx, p) in patterns[bit % patterns.len()].iter().enumerate() {
+                        if *p {
+                            words[idx] |= 1 << (bit % nbit);
                         }
                     }
                 }
                 simulation.add_words(words);
+                // only the words splitting no class are dropped here, the
+                // counterexamples of this loop must not be lost or it may
+                // not terminate.
+                if let Some(limit) = self.simulation_options.word_limit {
+                    if simulation.nword() > limit {
+                        let reps: Vec<AigEdge> = self
+                            .nodes_range_with_true()
+                            .map(|id| AigEdge::new(id, simulation[id].compl()))
+                            .collect();
+                        let keep = useful_words(&simulation, &reps, usize::MAX);
+                        if keep.len() < simulation.nword() {
+                            simulation.retain_words(&keep);
+                        }
+                    }
+                }
             }
         }
     }
@@ -392,7 +721,14 @@ impl Aig {
 
 #[cfg(test)]
 mod tests {
-    use crate::Aig;
+    use super::FrAig;
+    use crate::{
+        evaluate,
+        simulate::{RandomWordGenerator, SimulationWord},
+        symbolic_mc::TOTAL_FRAIG_ADD_SAT,
+        Aig, AigEdge,
+    };
+    use std::{collections::HashSet, mem::take};
 
     #[test]
     fn test1() {
@@ -407,4 +743,342 @@ mod tests {
         aig.fraig();
         assert_eq!(aig.fraig.unwrap().sim_map.keys().len(), 8);
     }
+
+    #[test]
+    fn test_fraig_rng_seed() {
+        let run = |seed: u64| {
+            let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+            aig.set_rng_seed(seed);
+            aig.fraig();
+            let fraig = aig.fraig.as_ref().unwrap();
+            let mut sim_map: Vec<(u64, Vec<AigEdge>)> =
+                fraig.sim_map.iter().map(|(k, v)| (*k, v.clone())).collect();
+            sim_map.sort();
+            (aig.num_nodes(), fraig.nword(), sim_map)
+        };
+        assert_eq!(run(3), run(3));
+        assert_ne!(run(3).2, run(4).2);
+    }
+
+    #[test]
+    fn test_fraig_with_constraints() {
+        let build = || {
+            let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+            let inputs = aig.inputs.clone();
+            let x = aig.new_and_node(inputs[0].into(), inputs[1].into());
+            let y = aig.new_and_node(inputs[0].into(), inputs[2].into());
+            aig.add_output(x);
+            aig.add_output(y);
+            let constraint = aig.new_equal_node(inputs[1].into(), inputs[2].into());
+            (aig, constraint)
+        };
+        let (mut aig, _) = build();
+        aig.fraig();
+        assert_ne!(aig.outputs[2], aig.outputs[3]);
+        let (mut aig, constraint) = build();
+        aig.fraig_with_constraints(&[constraint]);
+        assert_eq!(aig.outputs[2], aig.outputs[3]);
+        let constraint = aig.fraig.as_ref().unwrap().constraints()[0];
+        let simulation = &aig.fraig.as_ref().unwrap().simulation;
+        for i in 0..simulation.nword() {
+            let word = simulation[constraint.node_id()][i];
+            assert_eq!(if constraint.compl() { !word } else { word }, !0);
+        }
+    }
+
+    #[test]
+    fn test_fraig_class_query() {
+        let build = || {
+            let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+            let inputs = aig.inputs.clone();
+            let (a, b, c): (AigEdge, AigEdge, AigEdge) =
+                (inputs[0].into(), inputs[1].into(), inputs[2].into());
+            let ab = aig.new_and_node(a, b);
+            let bc = aig.new_and_node(b, c);
+            let ac = aig.new_and_node(a, c);
+            let x = aig.new_and_node(ab, c);
+            let y = aig.new_and_node(a, bc);
+            let z = aig.new_and_node(ac, b);
+            aig.add_output(x);
+            aig.add_output(y);
+            aig.add_output(z);
+            aig
+        };
+        let mut pairwise = build();
+        let npairwise = pairwise.fraig_refine(&[], false);
+        let mut aig = build();
+        let total = unsafe { TOTAL_FRAIG_ADD_SAT };
+        let nclass = aig.fraig_refine(&[], true);
+        assert!(unsafe { TOTAL_FRAIG_ADD_SAT } >= total + nclass);
+        assert!(nclass < npairwise);
+        assert_eq!(aig.num_nodes(), pairwise.num_nodes());
+        assert_eq!(aig.outputs, pairwise.outputs);
+        let n = aig.outputs.len();
+        assert_eq!(aig.outputs[n - 1], aig.outputs[n - 2]);
+        assert_eq!(aig.outputs[n - 1], aig.outputs[n - 3]);
+    }
+
+    #[test]
+    fn test_fraig_many_patterns() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        while aig.inputs.len() < 12 {
+            aig.new_input_node();
+        }
+        let inputs = aig.inputs.clone();
+        aig.set_rng_seed(5);
+        let simulation = aig.new_simulation(1);
+        let mut covered = vec![false; 1 << inputs.len()];
+        for i in 0..simulation.nword() {
+            for bit in 0..SimulationWord::BITS {
+                let minterm = inputs.iter().enumerate().fold(0, |m, (k, id)| {
+                    m | ((simulation[*id][i] >> bit & 1) as usize) << k
+                });
+                covered[minterm] = true;
+            }
+        }
+        // minterms missed by the initial patterns all simulate to false, so
+        // the first round splits them by more than 64 counterexamples.
+        let minterms: Vec<usize> = (0..covered.len())
+            .filter(|m| !covered[*m])
+            .take(70)
+            .collect();
+        assert_eq!(minterms.len(), 70);
+        for m in minterms {
+            let literals: Vec<AigEdge> = inputs
+                .iter()
+                .enumerate()
+                .map(|(k, id)| AigEdge::new(*id, m >> k & 1 == 0))
+                .collect();
+            let node = aig.new_and_nodes(literals);
+            aig.add_output(node);
+        }
+        let num_outputs = aig.outputs.len();
+        aig.set_rng_seed(5);
+        aig.fraig();
+        let outputs: HashSet<AigEdge> = aig.outputs[num_outputs - 70..].iter().copied().collect();
+        assert_eq!(outputs.len(), 70);
+        assert!(!outputs.contains(&AigEdge::constant_edge(false)));
+        let simulation = &aig.fraig.as_ref().unwrap().simulation;
+        assert!(simulation.nword() > SimulationWord::BITS as usize + 1);
+        let word = |e: AigEdge, i: usize| {
+            let word = simulation[e.node_id()][i];
+            if e.compl() {
+                !word
+            } else {
+                word
+            }
+        };
+        for node in aig.nodes.iter().filter(|n| n.is_and()) {
+            for i in 0..simulation.nword() {
+                assert_eq!(
+                    simulation[node.id][i],
+                    word(node.fanin0(), i) & word(node.fanin1(), i)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_cex_flips() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let inputs = aig.inputs.clone();
+        let constraint = aig.new_equal_node(inputs[1].into(), inputs[2].into());
+        aig.fraig_with_constraints(&[constraint]);
+        let constraint = aig.fraig.as_ref().unwrap().constraints()[0];
+        let cex: Vec<AigEdge> = [0, 1, 2]
+            .iter()
+            .map(|i| AigEdge::new(aig.inputs[*i], false))
+            .collect();
+        let fraig = aig.fraig.as_mut().unwrap();
+        let ncex = fraig.ncex;
+        fraig.add_cex(&aig.nodes, &cex);
+        assert_eq!(fraig.ncex, ncex + 2);
+        for bit in ncex..=ncex + 2 {
+            let word = fraig.lazy_cex[constraint.node_id()];
+            assert_eq!(word >> bit & 1 == 1, !constraint.compl());
+        }
+        assert_eq!(fraig.lazy_cex[cex[0].node_id()] >> (ncex + 1) & 1, 0);
+    }
+
+    #[test]
+    fn test_flip_pattern() {
+        let aig = Aig::from_file("aigs/counter.aag").unwrap();
+        let mut rng = RandomWordGenerator::new(0);
+        let pattern = FrAig::gen_pattern(&aig.nodes, &[], &mut rng);
+        let flipped = FrAig::flip_pattern(&aig.nodes, &pattern, aig.inputs[0]);
+        let values: Vec<AigEdge> = aig
+            .nodes_range()
+            .filter(|id| aig.nodes[*id].is_cinput())
+            .map(|id| AigEdge::new(id, !flipped[id]))
+            .collect();
+        let expect = evaluate(&aig.nodes, &values);
+        assert_ne!(flipped[aig.inputs[0]], pattern[aig.inputs[0]]);
+        for (id, value) in expect.iter().enumerate().skip(1) {
+            assert_eq!(flipped[id], value.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_lazy_resimulate() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.fraig();
+        let pattern: Vec<AigEdge> = aig
+            .nodes_range()
+            .filter(|id| aig.nodes[*id].is_cinput())
+            .map(|id| AigEdge::new(id, id % 3 == 0))
+            .collect();
+        let value = aig.evaluate(&pattern);
+        let fraig = aig.fraig.as_mut().unwrap();
+        let bit = fraig.ncex;
+        fraig.add_pattern(&aig.nodes, &pattern);
+        for (id, value) in value.iter().enumerate().skip(1) {
+            assert_eq!(fraig.lazy_cex[id] >> bit & 1 == 1, value.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_lazy_resimulate_and_values() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.fraig();
+        let cinputs: Vec<AigEdge> = aig
+            .nodes_range()
+            .filter(|id| aig.nodes[*id].is_cinput())
+            .map(|id| AigEdge::new(id, id % 2 == 0))
+            .collect();
+        let value = aig.evaluate(&cinputs);
+        let mut pattern = cinputs.clone();
+        pattern.extend(
+            aig.nodes_range()
+                .filter(|id| aig.nodes[*id].is_and() && *id % 3 == 0)
+                .map(|id| AigEdge::new(id, !value[id].unwrap())),
+        );
+        let fraig = aig.fraig.as_mut().unwrap();
+        let bit = fraig.ncex;
+        fraig.add_pattern(&aig.nodes, &pattern);
+        for (id, value) in value.iter().enumerate().skip(1) {
+            assert_eq!(fraig.lazy_cex[id] >> bit & 1 == 1, value.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_simulation_word_limit() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.fraig();
+        aig.set_simulation_word_limit(Some(64));
+        let cinputs: Vec<usize> = aig
+            .nodes_range()
+            .filter(|id| aig.nodes[*id].is_cinput())
+            .collect();
+        let fraig = aig.fraig.as_mut().unwrap();
+        let nword = fraig.nword();
+        for bit in 0..64 {
+            let pattern: Vec<AigEdge> = cinputs
+                .iter()
+                .map(|id| AigEdge::new(*id, (bit >> id) & 1 == 0))
+                .collect();
+            fraig.add_pattern(&aig.nodes, &pattern);
+        }
+        assert_eq!(fraig.nword(), nword);
+        for (hash, reps) in &fraig.sim_map {
+            for rep in reps {
+                assert_eq!(fraig.simulation.abs_hash_value(*rep), (*hash, false));
+            }
+        }
+        for node in aig.nodes.iter().filter(|n| n.is_and()) {
+            let lazy = |e: AigEdge| {
+                let word = fraig.lazy_cex[e.node_id()];
+                if e.compl() {
+                    !word
+                } else {
+                    word
+                }
+            };
+            assert_eq!(
+                fraig.lazy_cex[node.id],
+                lazy(node.fanin0()) & lazy(node.fanin1())
+            );
+        }
+        let x = aig.new_and_node(aig.inputs[0].into(), aig.latchs[0].input.into());
+        let num_nodes = aig.num_nodes();
+        let y = aig.new_and_node(aig.latchs[0].input.into(), aig.inputs[0].into());
+        assert_eq!(x, y);
+        let sat = unsafe { TOTAL_FRAIG_ADD_SAT };
+        let z = aig.new_and_node(x, aig.inputs[0].into());
+        assert_eq!(z, x);
+        assert!(unsafe { TOTAL_FRAIG_ADD_SAT } > sat);
+        assert_eq!(aig.num_nodes(), num_nodes);
+    }
+
+    #[test]
+    fn test_simulation_word_limit_amortized() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.fraig();
+        aig.set_simulation_word_limit(Some(256));
+        let cinputs: Vec<usize> = aig
+            .nodes_range()
+            .filter(|id| aig.nodes[*id].is_cinput())
+            .collect();
+        let fraig = aig.fraig.as_mut().unwrap();
+        let mut ncut = 0;
+        let mut nword = fraig.nword();
+        for bit in 0..200 * SimulationWord::BITS as usize {
+            let pattern: Vec<AigEdge> = cinputs
+                .iter()
+                .map(|id| AigEdge::new(*id, (bit >> (id % 8)) & 1 == 0))
+                .collect();
+            fraig.add_pattern(&aig.nodes, &pattern);
+            assert!(fraig.nword() <= 256);
+            if fraig.nword() < nword {
+                assert!(fraig.nword() <= 128);
+                ncut += 1;
+            }
+            nword = fraig.nword();
+        }
+        assert!(ncut > 0 && ncut <= 2);
+    }
+
+    #[test]
+    fn test_fraig_simulation_threads() {
+        let mut expect = Aig::from_file("aigs/counter.aag").unwrap();
+        expect.fraig();
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.set_simulation_threads(2);
+        aig.fraig();
+        let (fraig, expect_fraig) = (aig.fraig.as_ref().unwrap(), expect.fraig.as_ref().unwrap());
+        assert_eq!(fraig.nword(), expect_fraig.nword());
+        assert_eq!(fraig.sim_map, expect_fraig.sim_map);
+        assert_eq!(aig.num_nodes(), expect.num_nodes());
+    }
+
+    #[test]
+    fn test_simulation_word_limit_policy() {
+        let mut expect = Aig::from_file("aigs/counter.aag").unwrap();
+        expect.fraig();
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.set_simulation_word_limit(Some(64));
+        aig.fraig();
+        assert!(aig.fraig.as_ref().unwrap().nword() <= 64);
+        assert_eq!(aig.num_nodes(), expect.num_nodes());
+        aig.rewrite();
+        assert!(aig.fraig.is_none());
+        assert_eq!(aig.simulation_word_limit(), Some(64));
+        aig.fraig();
+        assert_eq!(aig.fraig.as_ref().unwrap().word_limit, Some(64));
+        assert!(aig.fraig.as_ref().unwrap().nword() <= 64);
+    }
+
+    #[test]
+    fn test_sim_map_collision() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        aig.fraig();
+        let inputs = aig.inputs.clone();
+        let x = aig.new_and_node(inputs[0].into(), !AigEdge::from(inputs[2]));
+        let fraig = aig.fraig.as_mut().unwrap();
+        let hash = fraig.simulation.abs_hash_value(x).0;
+        let reps: Vec<AigEdge> = take(&mut fraig.sim_map).into_values().flatten().collect();
+        fraig.sim_map.insert(hash, reps);
+        let z = aig.new_and_node(!AigEdge::from(inputs[2]), inputs[0].into());
+        assert_eq!(z, x);
+        aig.fraig.as_mut().unwrap().submit_lazy();
+    }
 }
@@ -0,0 +1,359 @@
+use crate::cut::{TruthTable, TRUTH_MAX_VARS, TRUTH_VARS};
+use std::{
+    collections::HashMap,
+    io::{self, Error, ErrorKind},
+    iter::Peekable,
+    path::Path,
+    str::Chars,
+};
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+#[derive(Debug, Clone)]
+pub struct Gate {
+    name: String,
+    area: f64,
+    output: String,
+    pins: Vec<String>,
+    delays: Vec<f64>,
+    truth: TruthTable,
+}
+
+impl Gate {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// input pins in the order they first appear in the expression, pin i is
+    /// variable i of the truth table.
+    pub fn pins(&self) -> &[String] {
+        &self.pins
+    }
+
+    /// block delay of every input pin, the larger of rise and fall.
+    pub fn delays(&self) -> &[f64] {
+        &self.delays
+    }
+
+    pub fn truth(&self) -> TruthTable {
+        self.truth
+    }
+}
+
+struct ExprParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    pins: Vec<String>,
+}
+
+impl<'a> ExprParser<'a> {
+    fn skip_space(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.peek().copied()
+    }
+
+    fn is_ident(c: char) -> bool {
+        c.is_alphanumeric() || "_[].".contains(c)
+    }
+
+    fn parse_or(&mut self) -> io::Result<TruthTable> {
+        let mut truth = self.parse_xor()?;
+        while let Some('+' | '|') = self.peek() {
+            self.chars.next();
+            truth |= self.parse_xor()?;
+        }
+        Ok(truth)
+    }
+
+    fn parse_xor(&mut self) -> io::Result<TruthTable> {
+        let mut truth = self.parse_and()?;
+        while let Some('^') = self.peek() {
+            self.chars.next();
+            truth ^= self.parse_and()?;
+        }
+        Ok(truth)
+    }
+
+    fn parse_and(&mut self) -> io::Result<TruthTable> {
+        let mut truth = self.parse_factor()?;
+        loop {
+            match self.peek() {
+                Some('*' | '&') => {
+                    self.chars.next();
+                }
+                Some(c) if c == '!' || c == '(' || Self::is_ident(c) => (),
+                _ => return Ok(truth),
+            }
+            truth &= self.parse_factor()?;
+        }
+    }
+
+    fn parse_factor(&mut self) -> io::Result<TruthTable> {
+        let mut truth = match self.peek() {
+            Some('!') => {
+                self.chars.next();
+                !self.parse_factor()?
+            }
+            Some('(') => {
+                self.chars.next();
+                let truth = self.parse_or()?;
+                if self.peek() != Some(')') {
+                    return Err(invalid("missing ')' in gate expression".to_string()));
+                }
+                self.chars.next();
+                truth
+            }
+            Some(c) if Self::is_ident(c) => {
+                let mut ident = String::new();
+                while let Some(c) = self.chars.next_if(|c| Self::is_ident(*c)) {
+                    ident.push(c);
+                }
+                match ident.as_str() {
+                    "CONST0" => 0,
+                    "CONST1" => !0,
+                    _ => {
+                        let var = match self.pins.iter().position(|p| *p == ident) {
+                            Some(var) => var,
+                            None => {
+                                self.pins.push(ident);
+                                self.pins.len() - 1
+                            }
+                        };
+                        if var >= TRUTH_MAX_VARS {
+                            return Err(invalid(format!(
+                                "gate with more than {} inputs",
+                                TRUTH_MAX_VARS
+                            )));
+                        }
+                        TRUTH_VARS[var]
+                    }
+                }
+            }
+            c => return Err(invalid(format!("unexpected {:?} in gate expression", c))),
+        };
+        while let Some('\'') = self.peek() {
+            self.chars.next();
+            truth = !truth;
+        }
+        Ok(truth)
+    }
+}
+
+/// A genlib cell library with its gates matched by truth table.
+#[derive(Debug, Clone)]
+pub struct Library {
+    gates: Vec<Gate>,
+    matches: HashMap<(usize, TruthTable), (usize, Vec<usize>)>,
+    inverter: usize,
+}
+
+/// truth table of the gate when pin i is driven by variable `perm[i]`.
+fn permute_truth(truth: TruthTable, perm: &[usize]) -> TruthTable {
+    let mut ret = 0;
+    for minterm in 0..1 << TRUTH_MAX_VARS {
+        let mut src = 0;
+        for (i, p) in perm.iter().enumerate() {
+            src |= (minterm >> p & 1) << i;
+        }
+        ret |= (truth >> src & 1) << minterm;
+    }
+    ret
+}
+
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut ret = Vec::new();
+    for perm in permutations(n - 1) {
+        for pos in 0..n {
+            let mut perm = perm.clone();
+            perm.insert(pos, n - 1);
+            ret.push(perm);
+        }
+    }
+    ret
+}
+
+impl Library {
+    fn parse_gate(tokens: &[&str]) -> io::Result<Gate> {
+        if tokens.len() < 4 {
+            return Err(invalid(format!("incomplete gate {:?}", tokens)));
+        }
+        let name = tokens[1].to_string();
+        let area: f64 = tokens[2]
+            .parse()
+            .map_err(|_| invalid(format!("bad area of gate {}", name)))?;
+        let function = tokens[3..].join(" ");
+        let (output, expr) = function
+            .trim_end_matches(';')
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("bad function of gate {}", name)))?;
+        let mut parser = ExprParser {
+            chars: expr.chars().peekable(),
+            pins: Vec::new(),
+        };
+        let truth = parser.parse_or()?;
+        if parser.peek().is_some() {
+            return Err(invalid(format!("bad function of gate {}", name)));
+        }
+        let delays = vec![1.0; parser.pins.len()];
+        Ok(Gate {
+            name,
+            area,
+            output: output.trim().to_string(),
+            pins: parser.pins,
+            delays,
+            truth,
+        })
+    }
+
+    fn parse_pin(gate: &mut Gate, tokens: &[&str]) -> io::Result<()> {
+        if tokens.len() != 9 {
+            return Err(invalid(format!("bad pin of gate {}", gate.name)));
+        }
+        let rise: f64 = tokens[5]
+            .parse()
+            .map_err(|_| invalid(format!("bad pin delay of gate {}", gate.name)))?;
+        let fall: f64 = tokens[7]
+            .parse()
+            .map_err(|_| invalid(format!("bad pin delay of gate {}", gate.name)))?;
+        for (pin, delay) in gate.pins.iter().zip(gate.delays.iter_mut()) {
+            if tokens[1] == "*" || tokens[1] == pin {
+                *delay = rise.max(fall);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let text: String = text
+            .lines()
+            .map(|l| l.split('#').next().unwrap())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let mut gates: Vec<Gate> = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                "GATE" => {
+                    let end = (i..tokens.len())
+                        .find(|j| tokens[*j].ends_with(';'))
+                        .ok_or_else(|| invalid("missing ';' after gate function".to_string()))?;
+                    gates.push(Self::parse_gate(&tokens[i..=end])?);
+                    i = end + 1;
+                }
+                "PIN" => {
+                    let gate = gates
+                        .last_mut()
+                        .ok_or_else(|| invalid("pin outside of a gate".to_string()))?;
+                    let end = (i + 9).min(tokens.len());
+                    Self::parse_pin(gate, &tokens[i..end])?;
+                    i = end;
+                }
+                token => return Err(invalid(format!("unsupported genlib statement {}", token))),
+            }
+        }
+        Self::new(gates)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(file: P) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(file)?)
+    }
+
+    fn new(gates: Vec<Gate>) -> io::Result<Self> {
+        let mut matches: HashMap<(usize, TruthTable), (usize, Vec<usize>)> = HashMap::new();
+        for (g, gate) in gates.iter().enumerate() {
+            let n = gate.pins.len();
+            if n == 0 {
+                continue;
+            }
+            for perm in permutations(n) {
+                let key = (n, permute_truth(gate.truth, &perm));
+                if matches
+                    .get(&key)
+                    .is_none_or(|(m, _)| gate.area < gates[*m].area)
+                {
+                    matches.insert(key, (g, perm));
+                }
+            }
+        }
+        let inverter = matches
+            .get(&(1, !TRUTH_VARS[0]))
+            .ok_or_else(|| invalid("library has no inverter".to_string()))?
+            .0;
+        let complete = (0..4).any(|mask: usize| {
+            let a = if mask & 1 == 1 {
+                !TRUTH_VARS[0]
+            } else {
+                TRUTH_VARS[0]
+            };
+            let b = if mask & 2 == 2 {
+                !TRUTH_VARS[1]
+            } else {
+                TRUTH_VARS[1]
+            };
+            matches.contains_key(&(2, a & b)) || matches.contains_key(&(2, !(a & b)))
+        });
+        if !complete {
+            return Err(invalid(
+                "library has no two input and or or gate".to_string(),
+            ));
+        }
+        Ok(Self {
+            gates,
+            matches,
+            inverter,
+        })
+    }
+
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    pub fn inverter(&self) -> usize {
+        self.inverter
+    }
+
+    /// the cheapest gate implementing the truth table over n variables and
+    /// the variable driving each of its pins.
+    pub fn find(&self, n: usize, truth: TruthTable) -> Option<(usize, &[usize])> {
+        self.matches
+            .get(&(n, truth))
+            .map(|(g, perm)| (*g, perm.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Library;
+    use crate::cut::TRUTH_VARS;
+
+    #[test]
+    fn test_genlib() {
+        let lib = Library::from_file("aigs/simple.genlib").unwrap();
+        assert_eq!(lib.gates().len(), 7);
+        assert_eq!(lib.gates()[lib.inverter()].name(), "inv1");
+        let (nand, _) = lib.find(2, !(TRUTH_VARS[0] & TRUTH_VARS[1])).unwrap();
+        assert_eq!(lib.gates()[nand].name(), "nand2");
+        let (aoi, _) = lib
+            .find(3, !(TRUTH_VARS[0] & TRUTH_VARS[1] | TRUTH_VARS[2]))
+            .unwrap();
+        assert_eq!(lib.gates()[aoi].delays(), [1.2, 1.2, 1.0]);
+        assert!(Library::parse("GATE and2 2 O=a*b;").is_err());
+    }
+}
@@ -1,22 +1,43 @@
 #![feature(assert_matches, ptr_metadata, unchecked_math, test, portable_simd)]
 
+mod activity;
 mod aiger;
+mod balance;
 mod brute_force;
+mod cellmap;
+mod coi;
+mod cut;
 mod display;
 mod eliminate;
 mod fraig;
+mod genlib;
+mod lut;
+mod mffc;
 mod migrate;
+mod reorder;
+mod resub;
+mod retime;
+mod rewrite;
 mod sat;
 mod sat_smc;
+mod scorr;
 mod simulate;
+mod stats;
 mod strash;
 mod symbolic_mc;
+mod ternary;
+mod truth;
+mod vcd;
+mod view;
 
+use aiger::AigSymbols;
 use fraig::FrAig;
+use rand::rngs::StdRng;
 use sat::SatSolver;
+use simulate::SimulationOptions;
 use std::{
     cmp::Reverse,
-    collections::BinaryHeap,
+    collections::{BinaryHeap, HashMap},
     hash::Hash,
     mem::{swap, take},
     ops::{Index, Not, Range},
@@ -33,7 +54,7 @@ pub enum AigNodeType {
     And(AigEdge, AigEdge),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AigNode {
     id: AigNodeId,
     level: usize,
@@ -201,6 +222,83 @@ impl AigEdge {
     }
 }
 
+fn map_edge(map: &[Option<AigEdge>], edge: AigEdge) -> AigEdge {
+    let mapped = map[edge.node_id()].unwrap();
+    if edge.compl() {
+        !mapped
+    } else {
+        mapped
+    }
+}
+
+fn fanin_logic_cone<'a, I: IntoIterator<Item = &'a AigEdge>>(
+    nodes: &[AigNode],
+    logic: I,
+) -> Vec<bool> {
+    let mut flag = vec![false; nodes.len()];
+    for l in logic {
+        flag[l.node_id()] = true;
+    }
+    for id in (0..nodes.len()).rev() {
+        if flag[id] && nodes[id].is_and() {
+            flag[nodes[id].fanin0().node_id()] = true;
+            flag[nodes[id].fanin1().node_id()] = true;
+        }
+    }
+    flag
+}
+
+fn fanout_logic_cone(nodes: &[AigNode], logic: AigEdge) -> Vec<bool> {
+    let mut flag = vec![false; nodes.len()];
+    flag[logic.node_id()] = true;
+    for id in 0..nodes.len() {
+        if flag[id] {
+            for f in &nodes[id].fanouts {
+                flag[f.node_id()] = true;
+            }
+        }
+    }
+    flag
+}
+
+/// three valued evaluation of the nodes under the partial assignment.
+fn evaluate(nodes: &[AigNode], values: &[AigEdge]) -> Vec<Option<bool>> {
+    let mut map = vec![None; nodes.len()];
+    map[0] = Some(false);
+    for value in values {
+        map[value.node_id()] = Some(!value.compl());
+    }
+    for id in 0..nodes.len() {
+        if map[id].is_none() {
+            if nodes[id].is_and() {
+                let fanin0 = nodes[id].fanin0();
+                let fanin1 = nodes[id].fanin1();
+                let fanin0_value = map[fanin0.node_id()].map(|v| v ^ fanin0.compl());
+                let fanin1_value = map[fanin1.node_id()].map(|v| v ^ fanin1.compl());
+                map[id] = match (fanin0_value, fanin1_value) {
+                    (None, None) => None,
+                    (None, Some(v)) => {
+                        if v {
+                            None
+                        } else {
+                            Some(v)
+                        }
+                    }
+                    (Some(v), None) => {
+                        if v {
+                            None
+                        } else {
+                            Some(v)
+                        }
+                    }
+                    (Some(v0), Some(v1)) => Some(v0 & v1),
+                }
+            }
+        }
+    }
+    map
+}
+
 #[derive(Debug, Clone)]
 pub struct AigLatch {
     input: AigNodeId,
@@ -225,9 +323,32 @@ pub struct Aig {
     // strash: Strash,
     fraig: Option<FrAig>,
     sat_solver: Box<dyn SatSolver>,
+    rng: StdRng,
+    symbols: AigSymbols,
+    simulation_options: SimulationOptions,
 }
 
-impl Aig {}
+impl Aig {
+    /// A fully independent copy of the design. The SAT solver is rebuilt from
+    /// the nodes, the FRAIG simulation and its class map are copied.
+    pub fn duplicate(&self) -> Self {
+        let mut aig = Self {
+            nodes: self.nodes.clone(),
+            inputs: self.inputs.clone(),
+            latchs: self.latchs.clone(),
+            outputs: self.outputs.clone(),
+            bads: self.bads.clone(),
+            num_ands: self.num_ands,
+            fraig: self.fraig.clone(),
+            sat_solver: Box::new(sat::abc_glucose::Solver::new()),
+            rng: self.rng.clone(),
+            symbols: self.symbols.clone(),
+            simulation_options: self.simulation_options,
+        };
+        aig.setup_sat_solver();
+        aig
+    }
+}
 
 impl Aig {
     // fn new() -> Self {
@@ -428,30 +549,11 @@ impl Aig {
     }
 
     pub fn fanin_logic_cone<'a, I: IntoIterator<Item = &'a AigEdge>>(&self, logic: I) -> Vec<bool> {
-        let mut flag = vec![false; self.num_nodes()];
-        for l in logic {
-            flag[l.node_id()] = true;
-        }
-        for id in self.nodes_range_with_true().rev() {
-            if flag[id] && self.nodes[id].is_and() {
-                flag[self.nodes[id].fanin0().node_id()] = true;
-                flag[self.nodes[id].fanin1().node_id()] = true;
-            }
-        }
-        flag
+        fanin_logic_cone(&self.nodes, logic)
     }
 
     pub fn fanout_logic_cone(&self, logic: AigEdge) -> Vec<bool> {
-        let mut flag = vec![false; self.num_nodes()];
-        flag[logic.node_id()] = true;
-        for id in self.nodes_range_with_true() {
-            if flag[id] {
-                for f in &self.nodes[id].fanouts {
-                    flag[f.node_id()] = true;
-                }
-            }
-        }
-        flag
+        fanout_logic_cone(&self.nodes, logic)
     }
 }
 
@@ -467,6 +569,9 @@ impl Aig {
         for i in &self.inputs {
             observe.push((*i).into());
         }
+        if let Some(fraig) = &self.fraig {
+            observe.extend(fraig.constraints());
+        }
         let mut observe = self.fanin_logic_cone(&observe);
         observe[0] = true;
         self.num_ands = 0;
@@ -526,6 +631,168 @@ impl Aig {
         }
         node_map
     }
+
+    fn rebuild_and_node(
+        &mut self,
+        strash: &mut HashMap<(AigEdge, AigEdge), AigEdge>,
+        mut fanin0: AigEdge,
+        mut fanin1: AigEdge,
+    ) -> AigEdge {
+        if fanin0.node_id() > fanin1.node_id() {
+            swap(&mut fanin0, &mut fanin1);
+        }
+        if let Some(edge) = strash.get(&(fanin0, fanin1)) {
+            return *edge;
+        }
+        let edge = self.new_and_node(fanin0, fanin1);
+        strash.insert((fanin0, fanin1), edge);
+        edge
+    }
+
+    pub(crate) fn roots(&self) -> Vec<AigEdge> {
+        let mut roots: Vec<AigEdge> = self.outputs.clone();
+        roots.extend(&self.bads);
+        roots.extend(self.latchs.iter().map(|l| l.next));
+        roots
+    }
+
+    /// Drop the nodes from `num_nodes` on, temporary logic built for SAT
+    /// queries. The SAT solver only forgets them and keeps what it learned.
+    /// The nodes must not be used by the interface or the FRAIG state.
+    pub(crate) fn truncate_nodes(&mut self, num_nodes: usize) {
+        if self.num_nodes() == num_nodes {
+            return;
+        }
+        let ands = self.nodes[num_nodes..]
+            .iter()
+            .filter(|n| n.is_and())
+            .count();
+        self.nodes.truncate(num_nodes);
+        self.num_ands -= ands;
+        for node in &mut self.nodes {
+            node.fanouts.retain(|f| f.node_id() < num_nodes);
+        }
+        self.sat_solver.truncate_nodes(num_nodes);
+    }
+
+    /// Keep the latchs satisfying `f` together with their symbols.
+    pub(crate) fn retain_latchs<F: FnMut(&AigLatch) -> bool>(&mut self, mut f: F) {
+        let positions: Vec<usize> = (0..self.latchs.len())
+            .filter(|i| f(&self.latchs[*i]))
+            .collect();
+        self.latchs = positions.iter().map(|i| self.latchs[*i].clone()).collect();
+        AigSymbols::select(&mut self.symbols.latchs, &positions);
+    }
+
+    /// Take the nodes out and restart the graph with only the constant and
+    /// the inputs and latch inputs, kept in their original order. Input nodes
+    /// that are neither an input nor a latch are dropped. The FRAIG state is
+    /// dropped, the symbols are kept. Returns the old nodes and the map of
+    /// their inputs.
+    pub(crate) fn take_nodes(&mut self) -> (Vec<AigNode>, Vec<Option<AigNodeId>>) {
+        let mut kept = vec![false; self.num_nodes()];
+        for input in &self.inputs {
+            kept[*input] = true;
+        }
+        for latch in &self.latchs {
+            kept[latch.input] = true;
+        }
+        let old_nodes = take(&mut self.nodes);
+        self.num_ands = 0;
+        self.fraig = None;
+        self.sat_solver = Box::new(sat::abc_glucose::Solver::new());
+        self.nodes.push(AigNode::new_false(0));
+        let mut input_map = vec![None; old_nodes.len()];
+        input_map[0] = Some(0);
+        for node in &old_nodes {
+            if kept[node.id] {
+                let id = self.nodes.len();
+                self.nodes.push(if node.is_prime_input() {
+                    AigNode::new_prime_input(id)
+                } else {
+                    AigNode::new_latch_input(id)
+                });
+                self.sat_solver.add_input_node(id);
+                input_map[node.id] = Some(id);
+            }
+        }
+        (old_nodes, input_map)
+    }
+
+    /// Move the inputs, latchs, outputs and bad states to the new nodes. Their
+    /// positions are kept, and so are the symbols naming them.
+    pub(crate) fn remap_interface(
+        &mut self,
+        input_map: &[Option<AigNodeId>],
+        map: &[Option<AigEdge>],
+    ) {
+        debug_assert!(self.symbols.fits(self));
+        for latch in &mut self.latchs {
+            latch.input = input_map[latch.input].unwrap();
+            latch.next = map_edge(map, latch.next);
+        }
+        for input in &mut self.inputs {
+            *input = input_map[*input].unwrap();
+        }
+        for out in &mut self.outputs {
+            *out = map_edge(map, *out);
+        }
+        for bad in &mut self.bads {
+            *bad = map_edge(map, *bad);
+        }
+    }
+
+    /// Rebuild the graph in topological order from the outputs, bad states
+    /// and latch next states, where `replace[id]` (if any) substitutes node
+    /// `id`. Inputs and latchs are all kept, dangling AND nodes are dropped and
+    /// structurally equal ones are shared. The FRAIG state is dropped.
+    /// Returns the map from old nodes to edges of the new graph.
+    pub fn rebuild(&mut self, replace: &[Option<AigEdge>]) -> Vec<Option<AigEdge>> {
+        let roots = self.roots();
+        let (old_nodes, input_map) = self.take_nodes();
+        let replace = |id: AigNodeId| replace.get(id).copied().flatten();
+        let mut map = vec![None; old_nodes.len()];
+        for (id, input) in input_map.iter().enumerate() {
+            if let Some(input) = input {
+                if replace(id).is_none() {
+                    map[id] = Some(AigEdge::new(*input, false));
+                }
+            }
+        }
+        let mut strash = HashMap::new();
+        for root in roots {
+            let mut stack = vec![root.node_id()];
+            while let Some(&id) = stack.last() {
+                if map[id].is_some() {
+                    stack.pop();
+                    continue;
+                }
+                let fanins = match replace(id) {
+                    Some(by) => vec![by],
+                    None => vec![old_nodes[id].fanin0(), old_nodes[id].fanin1()],
+                };
+                let pending: Vec<AigNodeId> = fanins
+                    .iter()
+                    .map(|f| f.node_id())
+                    .filter(|f| map[*f].is_none())
+                    .collect();
+                if !pending.is_empty() {
+                    stack.extend(pending);
+                    continue;
+                }
+                map[id] = Some(if fanins.len() == 1 {
+                    map_edge(&map, fanins[0])
+                } else {
+                    let fanin0 = map_edge(&map, fanins[0]);
+                    let fanin1 = map_edge(&map, fanins[1]);
+                    self.rebuild_and_node(&mut strash, fanin0, fanin1)
+                });
+                stack.pop();
+            }
+        }
+        self.remap_interface(&input_map, &map);
+        map
+    }
 }
 
 impl Aig {
@@ -542,6 +809,7 @@ impl Aig {
         &mut self,
     ) -> (Vec<(AigNodeId, AigNodeId)>, AigEdge) {
         let latchs = take(&mut self.latchs);
+        self.symbols.latchs.clear();
         let mut equals = Vec::new();
         (
             latchs
@@ -560,40 +828,7 @@ impl Aig {
     }
 
     pub fn evaluate(&mut self, values: &[AigEdge]) -> Vec<Option<bool>> {
-        let mut map = vec![None; self.num_nodes()];
-        map[0] = Some(false);
-        for value in values {
-            map[value.node_id()] = Some(!value.compl());
-        }
-        for id in self.nodes_range_with_true() {
-            if map[id].is_none() {
-                if self.nodes[id].is_and() {
-                    let fanin0 = self.nodes[id].fanin0();
-                    let fanin1 = self.nodes[id].fanin1();
-                    let fanin0_value = map[fanin0.node_id()].map(|v| v ^ fanin0.compl());
-                    let fanin1_value = map[fanin1.node_id()].map(|v| v ^ fanin1.compl());
-                    map[id] = match (fanin0_value, fanin1_value) {
-                        (None, None) => None,
-                        (None, Some(v)) => {
-                            if v {
-                                None
-                            } else {
-                                Some(v)
-                            }
-                        }
-                        (Some(v), None) => {
-                            if v {
-                                None
-                            } else {
-                                Some(v)
-                            }
-                        }
-                        (Some(v0), Some(v1)) => Some(v0 & v1),
-                    }
-                }
-            }
-        }
-        map
+        evaluate(&self.nodes, values)
     }
 }
 
@@ -607,11 +842,70 @@ impl Index<AigNodeId> for Aig {
 
 #[cfg(test)]
 mod tests {
-    use crate::Aig;
+    use crate::{evaluate, Aig, AigEdge};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// the values of the outputs when input i has value `values[i]`.
+    pub(crate) fn evaluate_outputs(aig: &Aig, values: &[bool]) -> Vec<bool> {
+        let assigns: Vec<AigEdge> = aig
+            .inputs
+            .iter()
+            .zip(values.iter())
+            .map(|(i, v)| AigEdge::new(*i, !v))
+            .collect();
+        let value = evaluate(&aig.nodes, &assigns);
+        aig.outputs
+            .iter()
+            .map(|o| value[o.node_id()].unwrap() ^ o.compl())
+            .collect()
+    }
+
+    /// the values of the outputs under every input minterm.
+    pub(crate) fn output_truth_table(aig: &Aig) -> Vec<Vec<bool>> {
+        (0..1usize << aig.inputs.len())
+            .map(|minterm| {
+                let values: Vec<bool> = (0..aig.inputs.len())
+                    .map(|i| minterm >> i & 1 == 1)
+                    .collect();
+                evaluate_outputs(aig, &values)
+            })
+            .collect()
+    }
+
+    /// the values of the outputs under `npattern` random input patterns drawn
+    /// from a generator seeded with `seed`, so failures reproduce.
+    pub(crate) fn random_output_values(aig: &Aig, seed: u64, npattern: usize) -> Vec<Vec<bool>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..npattern)
+            .map(|_| {
+                let values: Vec<bool> = aig.inputs.iter().map(|_| rng.gen()).collect();
+                evaluate_outputs(aig, &values)
+            })
+            .collect()
+    }
+
     #[test]
     fn test_replace_node() {
         let mut aig = Aig::from_file("aigs/i10.aag").unwrap();
         println!("{}", aig);
         aig.fraig();
     }
+
+    #[test]
+    fn test_duplicate() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        aig.fraig();
+        let mut dup = aig.duplicate();
+        assert_eq!(dup.num_nodes(), aig.num_nodes());
+        assert_eq!(
+            dup.fraig.as_ref().unwrap().nword(),
+            aig.fraig.as_ref().unwrap().nword()
+        );
+        let inputs = dup.inputs.clone();
+        let x = dup.new_and_node(inputs[0].into(), inputs[1].into());
+        let y = dup.new_and_node(inputs[1].into(), inputs[0].into());
+        assert_eq!(x, y);
+        assert!(dup.sat_solver.equivalence_check(x, y).is_none());
+        assert!(dup.num_nodes() > aig.num_nodes());
+    }
 }
@@ -0,0 +1,407 @@
+use crate::{
+    cut::{Cut, TruthTable, TRUTH_MAX_VARS},
+    Aig, AigEdge, AigLatch, AigNodeId,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+const LUT_CUT_LIMIT: usize = 8;
+
+const LUT_FLOW_ROUNDS: usize = 2;
+
+const LUT_EXACT_ROUNDS: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct Lut {
+    root: AigNodeId,
+    leaves: Vec<AigNodeId>,
+    truth: TruthTable,
+}
+
+impl Lut {
+    pub fn root(&self) -> AigNodeId {
+        self.root
+    }
+
+    pub fn leaves(&self) -> &[AigNodeId] {
+        &self.leaves
+    }
+
+    /// truth table over the leaves, leaf i is variable i.
+    pub fn truth(&self) -> TruthTable {
+        self.truth
+    }
+}
+
+/// A k-LUT network over the nodes of the mapped aig, luts are in topological
+/// order.
+#[derive(Debug, Clone)]
+pub struct LutNetwork {
+    inputs: Vec<AigNodeId>,
+    latchs: Vec<AigLatch>,
+    outputs: Vec<AigEdge>,
+    bads: Vec<AigEdge>,
+    luts: Vec<Lut>,
+    depth: usize,
+}
+
+impl LutNetwork {
+    pub fn luts(&self) -> &[Lut] {
+        &self.luts
+    }
+
+    pub fn num_luts(&self) -> usize {
+        self.luts.len()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn names(&self) -> HashMap<AigNodeId, String> {
+        let mut names = HashMap::new();
+        names.insert(0, "n0".to_string());
+        for (i, input) in self.inputs.iter().enumerate() {
+            names.insert(*input, format!("i{}", i));
+        }
+        for (i, latch) in self.latchs.iter().enumerate() {
+            names.insert(latch.input, format!("l{}", i));
+        }
+        for lut in &self.luts {
+            names.insert(lut.root, format!("n{}", lut.root));
+        }
+        names
+    }
+
+    fn write_blif_buffer<W: Write>(
+        w: &mut W,
+        names: &HashMap<AigNodeId, String>,
+        edge: AigEdge,
+        name: &str,
+    ) -> io::Result<()> {
+        if edge.node_id() == 0 {
+            writeln!(w, ".names {}", name)?;
+            if edge.compl() {
+                writeln!(w, "1")?;
+            }
+        } else {
+            writeln!(w, ".names {} {}", names[&edge.node_id()], name)?;
+            writeln!(w, "{} 1", if edge.compl() { 0 } else { 1 })?;
+        }
+        Ok(())
+    }
+
+    /// Write the network as BLIF, inputs are named `i<k>`, latchs `l<k>`,
+    /// outputs `o<k>`, bad states `b<k>` and luts `n<node>`.
+    pub fn write_blif<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let names = self.names();
+        writeln!(w, ".model aig")?;
+        write!(w, ".inputs")?;
+        for i in 0..self.inputs.len() {
+            write!(w, " i{}", i)?;
+        }
+        writeln!(w)?;
+        write!(w, ".outputs")?;
+        for i in 0..self.outputs.len() {
+            write!(w, " o{}", i)?;
+        }
+        for i in 0..self.bads.len() {
+            write!(w, " b{}", i)?;
+        }
+        writeln!(w)?;
+        for (i, latch) in self.latchs.iter().enumerate() {
+            writeln!(w, ".latch l{}_next l{} {}", i, i, latch.init as u8)?;
+        }
+        writeln!(w, ".names n0")?;
+        for lut in &self.luts {
+            write!(w, ".names")?;
+            for leaf in &lut.leaves {
+                write!(w, " {}", names[leaf])?;
+            }
+            writeln!(w, " n{}", lut.root)?;
+            for minterm in 0..1usize << lut.leaves.len() {
+                if lut.truth >> minterm & 1 == 1 {
+                    for i in 0..lut.leaves.len() {
+                        write!(w, "{}", minterm >> i & 1)?;
+                    }
+                    writeln!(w, " 1")?;
+                }
+            }
+        }
+        for (i, out) in self.outputs.iter().enumerate() {
+            Self::write_blif_buffer(w, &names, *out, &format!("o{}", i))?;
+        }
+        for (i, bad) in self.bads.iter().enumerate() {
+            Self::write_blif_buffer(w, &names, *bad, &format!("b{}", i))?;
+        }
+        for (i, latch) in self.latchs.iter().enumerate() {
+            Self::write_blif_buffer(w, &names, latch.next, &format!("l{}_next", i))?;
+        }
+        writeln!(w, ".end")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LutMode {
+    Depth,
+    Flow,
+}
+
+struct LutMapState {
+    k: usize,
+    cuts: Vec<Vec<Cut>>,
+    best: Vec<Option<Cut>>,
+    arrival: Vec<usize>,
+    flow: Vec<f64>,
+    est_refs: Vec<f64>,
+    refs: Vec<usize>,
+    required: Vec<usize>,
+}
+
+impl Aig {
+    fn lut_cut_arrival(&self, state: &LutMapState, cut: &Cut) -> usize {
+        cut.leaves()
+            .iter()
+            .map(|l| state.arrival[*l])
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    fn lut_cut_flow(&self, state: &LutMapState, node: AigNodeId, cut: &Cut) -> f64 {
+        let leaves: f64 = cut.leaves().iter().map(|l| state.flow[*l]).sum();
+        (leaves + 1.0) / state.est_refs[node]
+    }
+
+    /// one mapping pass keeping the best priority cuts of every node.
+    fn lut_map_pass(&self, state: &mut LutMapState, mode: LutMode) {
+        for id in self.nodes_range() {
+            let node = &self.nodes[id];
+            if !node.is_and() {
+                continue;
+            }
+            let fanin0 = node.fanin0();
+            let fanin1 = node.fanin1();
+            let mut cuts: Vec<Cut> = state.best[id].iter().cloned().collect();
+            for c0 in &state.cuts[fanin0.node_id()] {
+                for c1 in &state.cuts[fanin1.node_id()] {
+                    let cut = match Cut::and(c0, fanin0.compl(), c1, fanin1.compl(), state.k) {
+                        Some(cut) => cut,
+                        None => continue,
+                    };
+                    if cuts.iter().any(|c| c.is_subset_of(cut.leaves())) {
+                        continue;
+                    }
+                    cuts.retain(|c| !cut.is_subset_of(c.leaves()));
+                    cuts.push(cut);
+                }
+            }
+            let mut keyed: Vec<(usize, f64, usize, Cut)> = cuts
+                .into_iter()
+                .map(|c| {
+                    (
+                        self.lut_cut_arrival(state, &c),
+                        self.lut_cut_flow(state, id, &c),
+                        c.len(),
+                        c,
+                    )
+                })
+                .collect();
+            let required = state.required[id];
+            keyed.sort_by(|x, y| {
+                let x_late = x.0 > required;
+                let y_late = y.0 > required;
+                let order = match mode {
+                    LutMode::Depth => (x.0, x.2).cmp(&(y.0, y.2)).then(x.1.total_cmp(&y.1)),
+                    LutMode::Flow => x.1.total_cmp(&y.1).then((x.0, x.2).cmp(&(y.0, y.2))),
+                };
+                x_late.cmp(&y_late).then(order)
+            });
+            keyed.truncate(LUT_CUT_LIMIT);
+            let (arrival, flow, _, best) = keyed[0].clone();
+            state.arrival[id] = arrival;
+            state.flow[id] = flow;
+            state.best[id] = Some(best);
+            state.cuts[id] = vec![Cut::trivial(id)];
+            state.cuts[id].extend(keyed.into_iter().map(|k| k.3));
+        }
+    }
+
+    fn lut_ref(&self, state: &mut LutMapState, node: AigNodeId) -> usize {
+        let mut area = 1;
+        let leaves = state.best[node].as_ref().unwrap().leaves().to_vec();
+        for leaf in leaves {
+            if self.nodes[leaf].is_and() {
+                if state.refs[leaf] == 0 {
+                    area += self.lut_ref(state, leaf);
+                }
+                state.refs[leaf] += 1;
+            }
+        }
+        area
+    }
+
+    fn lut_deref(&self, state: &mut LutMapState, node: AigNodeId) -> usize {
+        let mut area = 1;
+        let leaves = state.best[node].as_ref().unwrap().leaves().to_vec();
+        for leaf in leaves {
+            if self.nodes[leaf].is_and() {
+                state.refs[leaf] -= 1;
+                if state.refs[leaf] == 0 {
+                    area += self.lut_deref(state, leaf);
+                }
+            }
+        }
+        area
+    }
+
+    /// reference the current mapping from the roots and compute the
+    /// required times, returns the mapped depth.
+    fn lut_reference(&self, state: &mut LutMapState, depth: bool) -> usize {
+        state.refs.iter_mut().for_each(|r| *r = 0);
+        let roots = self.roots();
+        for root in &roots {
+            if self.nodes[root.node_id()].is_and() {
+                if state.refs[root.node_id()] == 0 {
+                    self.lut_ref(state, root.node_id());
+                }
+                state.refs[root.node_id()] += 1;
+            }
+        }
+        let max_arrival = roots
+            .iter()
+            .map(|r| state.arrival[r.node_id()])
+            .max()
+            .unwrap_or(0);
+        let target = if depth { max_arrival } else { usize::MAX };
+        state.required.iter_mut().for_each(|r| *r = usize::MAX);
+        for root in &roots {
+            state.required[root.node_id()] = target;
+        }
+        for id in self.nodes_range().rev() {
+            if state.refs[id] == 0 || !self.nodes[id].is_and() {
+                continue;
+            }
+            let required = state.required[id].saturating_sub(1);
+            for leaf in state.best[id].as_ref().unwrap().leaves() {
+                state.required[*leaf] = state.required[*leaf].min(required);
+            }
+        }
+        for id in self.nodes_range() {
+            state.est_refs[id] = (state.est_refs[id] + 2.0 * state.refs[id].max(1) as f64) / 3.0;
+        }
+        max_arrival
+    }
+
+    /// exact area recovery, every mapped node takes the cut with the
+    /// smallest area it alone references without breaking required times.
+    fn lut_exact_pass(&self, state: &mut LutMapState) {
+        for id in self.nodes_range() {
+            if !self.nodes[id].is_and() {
+                continue;
+            }
+            if state.refs[id] == 0 {
+                let best = state.best[id].clone().unwrap();
+                state.arrival[id] = self.lut_cut_arrival(state, &best);
+                continue;
+            }
+            self.lut_deref(state, id);
+            let old = state.best[id].clone().unwrap();
+            let mut choice: Option<(usize, usize, Cut)> = None;
+            let cuts = state.cuts[id][1..].to_vec();
+            for cut in cuts {
+                let arrival = self.lut_cut_arrival(state, &cut);
+                if arrival > state.required[id] {
+                    continue;
+                }
+                state.best[id] = Some(cut.clone());
+                let area = self.lut_ref(state, id);
+                self.lut_deref(state, id);
+                if choice.as_ref().is_none_or(|c| (area, arrival) < (c.0, c.1)) {
+                    choice = Some((area, arrival, cut));
+                }
+            }
+            let (arrival, cut) = match choice {
+                Some((_, arrival, cut)) => (arrival, cut),
+                None => (self.lut_cut_arrival(state, &old), old),
+            };
+            state.arrival[id] = arrival;
+            state.best[id] = Some(cut);
+            self.lut_ref(state, id);
+        }
+    }
+
+    /// Map the aig into a network of `k`-input luts with priority cuts, area
+    /// flow and exact area recovery. With `depth` the mapped depth is
+    /// minimized first and kept during area recovery.
+    pub fn lut_map(&self, k: usize, depth: bool) -> LutNetwork {
+        assert!((2..=TRUTH_MAX_VARS).contains(&k));
+        let num_nodes = self.num_nodes();
+        let mut state = LutMapState {
+            k,
+            cuts: (0..num_nodes).map(|id| vec![Cut::trivial(id)]).collect(),
+            best: vec![None; num_nodes],
+            arrival: vec![0; num_nodes],
+            flow: vec![0.0; num_nodes],
+            est_refs: self
+                .nodes
+                .iter()
+                .map(|n| n.fanouts.len().max(1) as f64)
+                .collect(),
+            refs: vec![0; num_nodes],
+            required: vec![usize::MAX; num_nodes],
+        };
+        let first = if depth { LutMode::Depth } else { LutMode::Flow };
+        self.lut_map_pass(&mut state, first);
+        self.lut_reference(&mut state, depth);
+        for _ in 0..LUT_FLOW_ROUNDS {
+            self.lut_map_pass(&mut state, LutMode::Flow);
+            self.lut_reference(&mut state, depth);
+        }
+        for _ in 0..LUT_EXACT_ROUNDS {
+            self.lut_exact_pass(&mut state);
+            self.lut_reference(&mut state, depth);
+        }
+        let max_depth = self.lut_reference(&mut state, depth);
+        let luts = self
+            .nodes_range()
+            .filter(|id| state.refs[*id] > 0 && self.nodes[*id].is_and())
+            .map(|id| {
+                let cut = state.best[id].as_ref().unwrap();
+                Lut {
+                    root: id,
+                    leaves: cut.leaves().to_vec(),
+                    truth: cut.truth(),
+                }
+            })
+            .collect();
+        LutNetwork {
+            inputs: self.inputs.clone(),
+            latchs: self.latchs.clone(),
+            outputs: self.outputs.clone(),
+            bads: self.bads.clone(),
+            luts,
+            depth: max_depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Aig;
+
+    #[test]
+    fn test_lut_map() {
+        let aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let network = aig.lut_map(4, true);
+        assert_eq!(network.num_luts(), 2);
+        assert_eq!(network.depth(), 1);
+        let mut blif = Vec::new();
+        network.write_blif(&mut blif).unwrap();
+        let blif = String::from_utf8(blif).unwrap();
+        assert!(blif.contains(".names i0 i1 n5"));
+        assert!(blif.ends_with(".end\n"));
+    }
+}
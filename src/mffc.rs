@@ -0,0 +1,132 @@
+use crate::{Aig, AigEdge, AigNodeId};
+
+/// follow the replacements of a pass until an edge that is not replaced.
+pub(crate) fn resolve_edge(replace: &[Option<AigEdge>], mut edge: AigEdge) -> AigEdge {
+    while let Some(Some(by)) = replace.get(edge.node_id()) {
+        edge = if edge.compl() { !*by } else { *by };
+    }
+    edge
+}
+
+impl Aig {
+    /// Number of references of every node, its fanouts plus the outputs, bad
+    /// states and latch nexts pointing to it.
+    pub fn reference_counts(&self) -> Vec<usize> {
+        let mut refs: Vec<usize> = self.nodes.iter().map(|n| n.fanouts.len()).collect();
+        for root in self.roots() {
+            refs[root.node_id()] += 1;
+        }
+        refs
+    }
+
+    /// `fanins(node)` gives the fanins of an and node to release, each with
+    /// whether it may be expanded once unreferenced, which lets callers
+    /// resolve replaced fanins and stop at cut leaves.
+    pub(crate) fn deref_node_with<F>(
+        &self,
+        refs: &mut [usize],
+        node: AigNodeId,
+        fanins: &F,
+        mffc: &mut Vec<AigNodeId>,
+    ) where
+        F: Fn(AigNodeId) -> [(AigEdge, bool); 2],
+    {
+        mffc.push(node);
+        for (fanin, expand) in fanins(node) {
+            refs[fanin.node_id()] -= 1;
+            if expand && refs[fanin.node_id()] == 0 && self.nodes[fanin.node_id()].is_and() {
+                self.deref_node_with(refs, fanin.node_id(), fanins, mffc);
+            }
+        }
+    }
+
+    pub(crate) fn ref_node_with<F>(&self, refs: &mut [usize], node: AigNodeId, fanins: &F)
+    where
+        F: Fn(AigNodeId) -> [(AigEdge, bool); 2],
+    {
+        for (fanin, expand) in fanins(node) {
+            if expand && refs[fanin.node_id()] == 0 && self.nodes[fanin.node_id()].is_and() {
+                self.ref_node_with(refs, fanin.node_id(), fanins);
+            }
+            refs[fanin.node_id()] += 1;
+        }
+    }
+
+    /// Release the references the and node holds on its fanins and
+    /// recursively on every fanin left unreferenced. Returns the maximum
+    /// fanout free cone of the node, the node first. Undone by `ref_node`.
+    pub fn deref_node(&self, refs: &mut [usize], node: AigNodeId) -> Vec<AigNodeId> {
+        assert!(self.nodes[node].is_and());
+        let mut mffc = Vec::new();
+        let fanins = |id: AigNodeId| {
+            [
+                (self.nodes[id].fanin0(), true),
+                (self.nodes[id].fanin1(), true),
+            ]
+        };
+        self.deref_node_with(refs, node, &fanins, &mut mffc);
+        mffc
+    }
+
+    pub fn ref_node(&self, refs: &mut [usize], node: AigNodeId) {
+        assert!(self.nodes[node].is_and());
+        let fanins = |id: AigNodeId| {
+            [
+                (self.nodes[id].fanin0(), true),
+                (self.nodes[id].fanin1(), true),
+            ]
+        };
+        self.ref_node_with(refs, node, &fanins);
+    }
+
+    /// The maximum fanout free cone of the and node, the and nodes that are
+    /// only used through it, the node first.
+    pub fn mffc(&self, node: AigNodeId) -> Vec<AigNodeId> {
+        let mut refs = self.reference_counts();
+        self.deref_node(&mut refs, node)
+    }
+
+    /// And nodes removed if the and node were replaced by `by`, the part of
+    /// its maximum fanout free cone that `by` does not use. `refs` is left
+    /// unchanged.
+    pub fn freed_nodes(&self, refs: &mut [usize], node: AigNodeId, by: AigEdge) -> Vec<AigNodeId> {
+        let mffc = self.deref_node(refs, node);
+        let by = by.node_id();
+        if refs[by] == 0 && self.nodes[by].is_and() {
+            self.ref_node(refs, by);
+        }
+        refs[by] += 1;
+        let freed = mffc
+            .iter()
+            .copied()
+            .filter(|id| *id == node || refs[*id] == 0)
+            .collect();
+        refs[by] -= 1;
+        if refs[by] == 0 && self.nodes[by].is_and() {
+            self.deref_node(refs, by);
+        }
+        self.ref_node(refs, node);
+        freed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Aig, AigEdge};
+
+    #[test]
+    fn test_mffc() {
+        let aig = Aig::from_file("aigs/odc.aag").unwrap();
+        assert_eq!(aig.mffc(9), [9, 8]);
+        assert_eq!(aig.mffc(7), [7]);
+        let mut mffc = aig.mffc(6);
+        mffc.sort();
+        assert_eq!(mffc, [4, 5, 6]);
+        let mut refs = aig.reference_counts();
+        let old = refs.clone();
+        let mut freed = aig.freed_nodes(&mut refs, 6, AigEdge::new(5, true));
+        freed.sort();
+        assert_eq!(freed, [4, 6]);
+        assert_eq!(refs, old);
+    }
+}
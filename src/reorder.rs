@@ -0,0 +1,128 @@
+use crate::{sat, Aig, AigEdge, AigNode, AigNodeId};
+use std::mem::take;
+
+impl Aig {
+    /// the new position of every node, the constant and the inputs and latch
+    /// inputs keep their relative order in front, and nodes follow in depth
+    /// first post order from the roots, dangling ones last.
+    fn dfs_order(&self) -> Vec<AigNodeId> {
+        let mut order: Vec<AigNodeId> = self
+            .nodes_range_with_true()
+            .filter(|id| !self.nodes[*id].is_and())
+            .collect();
+        let mut visited = vec![false; self.num_nodes()];
+        for id in &order {
+            visited[*id] = true;
+        }
+        let starts = self
+            .roots()
+            .into_iter()
+            .map(|e| e.node_id())
+            .chain(self.nodes_range());
+        for start in starts {
+            let mut stack = vec![(start, false)];
+            while let Some((id, expanded)) = stack.pop() {
+                if expanded {
+                    order.push(id);
+                    continue;
+                }
+                if visited[id] {
+                    continue;
+                }
+                visited[id] = true;
+                stack.push((id, true));
+                stack.push((self.nodes[id].fanin1().node_id(), false));
+                stack.push((self.nodes[id].fanin0().node_id(), false));
+            }
+        }
+        order
+    }
+
+    /// Renumber the nodes in depth first order from the outputs, bad states
+    /// and latch next states, keeping them topologically ordered so the cone
+    /// of a root gets close ids. No node is removed, the SAT solver is rebuilt
+    /// and the FRAIG state is remapped like in `cleanup_redundant`. The
+    /// interface keeps its positions, so the symbols stay valid. Returns the
+    /// map from old to new node ids.
+    pub fn reorder_dfs(&mut self) -> Vec<Option<AigNodeId>> {
+        let order = self.dfs_order();
+        let mut node_map = vec![None; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            node_map[*old] = Some(new);
+        }
+        let map_edge = |e: AigEdge| AigEdge::new(node_map[e.node_id()].unwrap(), e.compl());
+        let old_nodes = take(&mut self.nodes);
+        for (new, old) in order.iter().enumerate() {
+            let old = &old_nodes[*old];
+            let node = if old.is_and() {
+                AigNode::new_and(
+                    new,
+                    map_edge(old.fanin0()),
+                    map_edge(old.fanin1()),
+                    old.level,
+                )
+            } else {
+                AigNode {
+                    id: new,
+                    level: old.level,
+                    typ: old.typ.clone(),
+                    fanouts: Vec::new(),
+                }
+            };
+            self.nodes.push(node);
+        }
+        self.setup_fanouts();
+        for latch in &mut self.latchs {
+            latch.input = node_map[latch.input].unwrap();
+            latch.next = map_edge(latch.next);
+        }
+        for input in &mut self.inputs {
+            *input = node_map[*input].unwrap();
+        }
+        for out in &mut self.outputs {
+            *out = map_edge(*out);
+        }
+        for bad in &mut self.bads {
+            *bad = map_edge(*bad);
+        }
+        self.sat_solver = Box::new(sat::abc_glucose::Solver::new());
+        self.setup_sat_solver();
+        if let Some(fraig) = &mut self.fraig {
+            fraig.reorder(&order, &node_map);
+        }
+        node_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::random_output_values, Aig, AigEdge};
+
+    #[test]
+    fn test_reorder_dfs() {
+        let mut aig = Aig::from_file("aigs/i10.aag").unwrap();
+        let expect = random_output_values(&aig, 0, 64);
+        aig.reorder_dfs();
+        for node in aig.ands_iter() {
+            assert!(node.fanin0().node_id() < node.node_id());
+            assert!(node.fanin1().node_id() < node.node_id());
+        }
+        assert_eq!(random_output_values(&aig, 0, 64), expect);
+    }
+
+    #[test]
+    fn test_reorder_dfs_fraig() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        aig.fraig();
+        let inputs = aig.inputs.clone();
+        let x = aig.new_and_node(inputs[0].into(), !AigEdge::from(inputs[2]));
+        let y = aig.new_and_node(x, inputs[1].into());
+        aig.add_output(y);
+        let node_map = aig.reorder_dfs();
+        let num_nodes = aig.num_nodes();
+        let inputs = aig.inputs.clone();
+        let z = aig.new_and_node(!AigEdge::from(inputs[2]), inputs[0].into());
+        assert_eq!(z, AigEdge::new(node_map[x.node_id()].unwrap(), x.compl()));
+        assert_eq!(aig.num_nodes(), num_nodes);
+    }
+}
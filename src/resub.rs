@@ -0,0 +1,445 @@
+use crate::{mffc::resolve_edge, simulate::SimulationWord, Aig, AigEdge, AigNodeId};
+use std::collections::{HashMap, HashSet};
+
+const RESUB_DIVISOR_LEVELS: usize = 4;
+
+const RESUB_MAX_DIVISORS: usize = 32;
+
+const RESUB_ODC_LEVELS: usize = 3;
+
+const RESUB_SAT_LIMIT: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+enum ResubCandidate {
+    Edge(AigEdge),
+    And(AigEdge, AigEdge, bool),
+}
+
+struct ResubWindow {
+    nodes: Vec<AigNodeId>,
+    outputs: Vec<AigNodeId>,
+    care: Vec<SimulationWord>,
+}
+
+struct ResubState {
+    num_old: usize,
+    refs: Vec<usize>,
+    replace: Vec<Option<AigEdge>>,
+    tainted: Vec<bool>,
+    is_root: Vec<bool>,
+    words: Vec<Vec<SimulationWord>>,
+}
+
+impl ResubState {
+    fn resolve(&self, edge: AigEdge) -> AigEdge {
+        resolve_edge(&self.replace, edge)
+    }
+
+    fn edge_words(&self, edge: AigEdge, idx: usize) -> SimulationWord {
+        if edge.compl() {
+            !self.words[edge.node_id()][idx]
+        } else {
+            self.words[edge.node_id()][idx]
+        }
+    }
+
+    fn candidate_word(&self, candidate: ResubCandidate, idx: usize) -> SimulationWord {
+        match candidate {
+            ResubCandidate::Edge(e) => self.edge_words(e, idx),
+            ResubCandidate::And(x, y, compl) => {
+                let and = self.edge_words(x, idx) & self.edge_words(y, idx);
+                if compl {
+                    !and
+                } else {
+                    and
+                }
+            }
+        }
+    }
+
+    /// whether the candidate matches the node on the cared patterns, first
+    /// word is checked first since it rejects almost every candidate.
+    fn candidate_match(
+        &self,
+        node: AigNodeId,
+        candidate: ResubCandidate,
+        care: &[SimulationWord],
+    ) -> bool {
+        let target = &self.words[node];
+        (self.candidate_word(candidate, 0) ^ target[0]) & care[0] == 0
+            && (1..target.len())
+                .all(|i| (self.candidate_word(candidate, i) ^ target[i]) & care[i] == 0)
+    }
+}
+
+impl Aig {
+    fn resub_fanins(&self, state: &ResubState, node: AigNodeId) -> [AigEdge; 2] {
+        [
+            state.resolve(self.nodes[node].fanin0()),
+            state.resolve(self.nodes[node].fanin1()),
+        ]
+    }
+
+    fn resub_deref(&self, state: &mut ResubState, node: AigNodeId, mffc: &mut Vec<AigNodeId>) {
+        let replace = &state.replace;
+        let fanins = |id: AigNodeId| {
+            [
+                (resolve_edge(replace, self.nodes[id].fanin0()), true),
+                (resolve_edge(replace, self.nodes[id].fanin1()), true),
+            ]
+        };
+        self.deref_node_with(&mut state.refs, node, &fanins, mffc);
+    }
+
+    fn resub_ref(&self, state: &mut ResubState, node: AigNodeId) {
+        let replace = &state.replace;
+        let fanins = |id: AigNodeId| {
+            [
+                (resolve_edge(replace, self.nodes[id].fanin0()), true),
+                (resolve_edge(replace, self.nodes[id].fanin1()), true),
+            ]
+        };
+        self.ref_node_with(&mut state.refs, node, &fanins);
+    }
+
+    fn resub_divisor_valid(&self, state: &ResubState, node: AigNodeId, id: AigNodeId) -> bool {
+        id > 0
+            && id < node
+            && !state.tainted[id]
+            && (!self.nodes[id].is_and() || state.refs[id] > 0)
+    }
+
+    /// old nodes in the transitive fanin window of the node and the ones
+    /// computed from them, must be called with the mffc of the node
+    /// dereferenced.
+    fn resub_divisors(&self, state: &ResubState, node: AigNodeId) -> Vec<AigNodeId> {
+        let mut divisors = Vec::new();
+        let mut visited = HashSet::new();
+        let mut frontier = vec![node];
+        for _ in 0..RESUB_DIVISOR_LEVELS {
+            let mut next = Vec::new();
+            for id in frontier {
+                if !self.nodes[id].is_and() {
+                    continue;
+                }
+                for fanin in self.resub_fanins(state, id) {
+                    if visited.insert(fanin.node_id()) {
+                        next.push(fanin.node_id());
+                        if fanin.node_id() < state.num_old
+                            && self.resub_divisor_valid(state, node, fanin.node_id())
+                        {
+                            divisors.push(fanin.node_id());
+                        }
+                    }
+                }
+            }
+            frontier = next;
+        }
+        divisors.truncate(RESUB_MAX_DIVISORS);
+        let mut idx = 0;
+        while idx < divisors.len() && divisors.len() < RESUB_MAX_DIVISORS {
+            for fanout in &self.nodes[divisors[idx]].fanouts {
+                let id = fanout.node_id();
+                if id >= state.num_old
+                    || visited.contains(&id)
+                    || !self.resub_divisor_valid(state, node, id)
+                {
+                    continue;
+                }
+                if self
+                    .resub_fanins(state, id)
+                    .iter()
+                    .all(|f| divisors.contains(&f.node_id()))
+                {
+                    visited.insert(id);
+                    divisors.push(id);
+                }
+            }
+            idx += 1;
+        }
+        divisors.truncate(RESUB_MAX_DIVISORS);
+        divisors
+    }
+
+    fn resub_alive_fanouts<'a>(
+        &'a self,
+        state: &'a ResubState,
+        node: AigNodeId,
+    ) -> impl Iterator<Item = AigNodeId> + 'a {
+        self.nodes[node]
+            .fanouts
+            .iter()
+            .map(|f| f.node_id())
+            .filter(|f| *f < state.num_old && state.refs[*f] > 0)
+    }
+
+    /// transitive fanout window of the node and the patterns under which the
+    /// node is observable at the window outputs.
+    fn resub_window(&self, state: &ResubState, node: AigNodeId) -> Option<ResubWindow> {
+        let max_level = self.nodes[node].level + RESUB_ODC_LEVELS;
+        let mut in_window = HashSet::new();
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            for fanout in self.resub_alive_fanouts(state, id) {
+                if self.nodes[fanout].level <= max_level && in_window.insert(fanout) {
+                    stack.push(fanout);
+                }
+            }
+        }
+        let mut nodes: Vec<AigNodeId> = in_window.iter().copied().collect();
+        nodes.sort();
+        if nodes.iter().any(|n| state.tainted[*n]) {
+            return None;
+        }
+        let outputs: Vec<AigNodeId> = [node]
+            .iter()
+            .chain(nodes.iter())
+            .copied()
+            .filter(|n| {
+                state.is_root[*n]
+                    || self
+                        .resub_alive_fanouts(state, *n)
+                        .any(|f| !in_window.contains(&f))
+            })
+            .collect();
+        if outputs.is_empty() {
+            return None;
+        }
+        let mut flipped: HashMap<AigNodeId, Vec<SimulationWord>> = HashMap::new();
+        flipped.insert(node, state.words[node].iter().map(|w| !w).collect());
+        let nword = state.words[node].len();
+        for n in &nodes {
+            let fanin0 = self.nodes[*n].fanin0();
+            let fanin1 = self.nodes[*n].fanin1();
+            let word = |e: AigEdge, idx: usize| {
+                let w = match flipped.get(&e.node_id()) {
+                    Some(words) => words[idx],
+                    None => state.words[e.node_id()][idx],
+                };
+                if e.compl() {
+                    !w
+                } else {
+                    w
+                }
+            };
+            let words = (0..nword)
+                .map(|i| word(fanin0, i) & word(fanin1, i))
+                .collect();
+            flipped.insert(*n, words);
+        }
+        let mut care = vec![0; nword];
+        for o in &outputs {
+            for (i, c) in care.iter_mut().enumerate() {
+                *c |= state.words[*o][i] ^ flipped[o][i];
+            }
+        }
+        Some(ResubWindow {
+            nodes,
+            outputs,
+            care,
+        })
+    }
+
+    fn resub_build(&mut self, candidate: ResubCandidate) -> AigEdge {
+        match candidate {
+            ResubCandidate::Edge(e) => e,
+            ResubCandidate::And(x, y, compl) => {
+                let and = self.new_and_node(x, y);
+                if compl {
+                    !and
+                } else {
+                    and
+                }
+            }
+        }
+    }
+
+    fn resub_prove(
+        &mut self,
+        node: AigNodeId,
+        candidate: AigEdge,
+        window: Option<&ResubWindow>,
+    ) -> bool {
+        let window = match window {
+            Some(window) => window,
+            None => {
+                return if candidate.node_id() == 0 {
+                    self.sat_solver
+                        .solve(&[AigEdge::new(node, candidate.compl())])
+                        .is_none()
+                } else {
+                    self.sat_solver
+                        .equivalence_check(node.into(), candidate)
+                        .is_none()
+                };
+            }
+        };
+        let mut map: HashMap<AigNodeId, AigEdge> = HashMap::new();
+        map.insert(node, candidate);
+        for n in &window.nodes {
+            let mut fanins = [self.nodes[*n].fanin0(), self.nodes[*n].fanin1()];
+            for fanin in fanins.iter_mut() {
+                if let Some(by) = map.get(&fanin.node_id()) {
+                    *fanin = if fanin.compl() { !*by } else { *by };
+                }
+            }
+            let copy = self.new_and_node(fanins[0], fanins[1]);
+            map.insert(*n, copy);
+        }
+        let mut equals = Vec::new();
+        for o in &window.outputs {
+            equals.push(self.new_equal_node((*o).into(), map[o]));
+        }
+        let equal = self.new_and_nodes(equals);
+        self.sat_solver.solve(&[!equal]).is_none()
+    }
+
+    fn resub_taint(&self, state: &mut ResubState, node: AigNodeId) {
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            for fanout in &self.nodes[id].fanouts {
+                if fanout.node_id() < state.num_old && !state.tainted[fanout.node_id()] {
+                    state.tainted[fanout.node_id()] = true;
+                    stack.push(fanout.node_id());
+                }
+            }
+        }
+    }
+
+    fn resub_pass(&mut self, odc: bool) {
+        self.fraig = None;
+        let num_old = self.num_nodes();
+        let simulation = self.new_simulation(1);
+        let nword = simulation.nword();
+        let words = (0..num_old)
+            .map(|id| (0..nword).map(|i| simulation[id][i]).collect())
+            .collect();
+        let refs = self.reference_counts();
+        let mut is_root = vec![false; num_old];
+        for root in self.roots() {
+            is_root[root.node_id()] = true;
+        }
+        let mut state = ResubState {
+            num_old,
+            refs,
+            replace: vec![None; num_old],
+            tainted: vec![false; num_old],
+            is_root,
+            words,
+        };
+        for node in 1..num_old {
+            if !self.nodes[node].is_and() || state.refs[node] == 0 || state.tainted[node] {
+                continue;
+            }
+            state.refs.resize(self.num_nodes(), 0);
+            let mut mffc = Vec::new();
+            self.resub_deref(&mut state, node, &mut mffc);
+            let divisors = self.resub_divisors(&state, node);
+            self.resub_ref(&mut state, node);
+            let window = if odc {
+                self.resub_window(&state, node)
+            } else {
+                None
+            };
+            let full_care = vec![!0; nword];
+            let mut candidates = vec![
+                ResubCandidate::Edge(AigEdge::constant_edge(false)),
+                ResubCandidate::Edge(AigEdge::constant_edge(true)),
+            ];
+            for d in &divisors {
+                candidates.push(ResubCandidate::Edge((*d).into()));
+                candidates.push(ResubCandidate::Edge(!AigEdge::from(*d)));
+            }
+            if mffc.len() > 1 {
+                for (i, x) in divisors.iter().enumerate() {
+                    for y in &divisors[i + 1..] {
+                        for polarity in 0..4 {
+                            let x = AigEdge::new(*x, polarity & 1 == 1);
+                            let y = AigEdge::new(*y, polarity & 2 == 2);
+                            candidates.push(ResubCandidate::And(x, y, false));
+                            candidates.push(ResubCandidate::And(x, y, true));
+                        }
+                    }
+                }
+            }
+            let mut nsat = 0;
+            for candidate in candidates {
+                let exact = state.candidate_match(node, candidate, &full_care);
+                let window = match &window {
+                    Some(window) if !exact => {
+                        if !state.candidate_match(node, candidate, &window.care) {
+                            continue;
+                        }
+                        Some(window)
+                    }
+                    _ if !exact => continue,
+                    _ => None,
+                };
+                let by = self.resub_build(candidate);
+                if !self.resub_prove(node, by, window) {
+                    nsat += 1;
+                    if nsat == RESUB_SAT_LIMIT {
+                        break;
+                    }
+                    continue;
+                }
+                state.refs.resize(self.num_nodes(), 0);
+                self.resub_deref(&mut state, node, &mut Vec::new());
+                if let ResubCandidate::And(..) = candidate {
+                    for fanin in self.resub_fanins(&state, by.node_id()) {
+                        state.refs[fanin.node_id()] += 1;
+                    }
+                }
+                state.refs[by.node_id()] += state.refs[node];
+                state.refs[node] = 0;
+                state.replace[node] = Some(by);
+                if window.is_some() {
+                    self.resub_taint(&mut state, node);
+                }
+                break;
+            }
+        }
+        self.rebuild(&state.replace);
+    }
+
+    /// Re-express every node by a constant, a divisor or an and of two
+    /// divisors when it is cheaper than its mffc. Candidates are filtered by
+    /// simulation and proved by the sat solver. With `odc` the candidates
+    /// only have to match where the node is observable in a small fanout
+    /// window, nodes in the fanout of such a replacement are left to the
+    /// exact pass that follows. The FRAIG state is dropped.
+    pub fn resub(&mut self, odc: bool) {
+        self.resub_pass(false);
+        if odc {
+            self.resub_pass(true);
+            self.resub_pass(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::output_truth_table, Aig};
+
+    #[test]
+    fn test_resub_cec1() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        aig.resub(false);
+        assert_eq!(aig.num_ands, 0);
+        assert_eq!(aig.outputs[0], aig.outputs[1]);
+    }
+
+    #[test]
+    fn test_resub_odc() {
+        let origin = Aig::from_file("aigs/odc.aag").unwrap();
+        let mut exact = Aig::from_file("aigs/odc.aag").unwrap();
+        let mut odc = Aig::from_file("aigs/odc.aag").unwrap();
+        exact.resub(false);
+        odc.resub(true);
+        assert!(exact.num_ands < origin.num_ands);
+        assert!(odc.num_ands < origin.num_ands);
+        let truth = output_truth_table(&origin);
+        assert_eq!(truth, output_truth_table(&exact));
+        assert_eq!(truth, output_truth_table(&odc));
+    }
+}
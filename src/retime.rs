@@ -0,0 +1,291 @@
+use crate::{map_edge, Aig, AigEdge, AigLatch, AigNode, AigNodeId};
+use std::collections::VecDeque;
+
+const RETIME_INFINITE: usize = usize::MAX / 2;
+
+/// Relation between a retimed design and the original one. Inputs are kept
+/// in order, so an input trace of the retimed design drives the original one
+/// to the same outputs and bad states.
+#[derive(Debug, Clone)]
+pub struct RetimeMap {
+    /// latch i of the retimed design holds the value of original node
+    /// `latchs[i]` in every frame.
+    pub latchs: Vec<AigNodeId>,
+}
+
+struct FlowEdge {
+    to: usize,
+    cap: usize,
+    rev: usize,
+}
+
+struct FlowGraph {
+    adjs: Vec<Vec<FlowEdge>>,
+}
+
+impl FlowGraph {
+    const SOURCE: usize = 0;
+
+    const SINK: usize = 1;
+
+    fn new(nvertex: usize) -> Self {
+        Self {
+            adjs: (0..nvertex).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, cap: usize) {
+        let rev = self.adjs[to].len();
+        self.adjs[from].push(FlowEdge { to, cap, rev });
+        let rev = self.adjs[from].len() - 1;
+        self.adjs[to].push(FlowEdge {
+            to: from,
+            cap: 0,
+            rev,
+        });
+    }
+
+    /// residual breadth first search from the source, returns the parent
+    /// edges of the reached vertices.
+    fn search(&self) -> Vec<Option<(usize, usize)>> {
+        let mut parent = vec![None; self.adjs.len()];
+        parent[Self::SOURCE] = Some((Self::SOURCE, 0));
+        let mut queue = VecDeque::from([Self::SOURCE]);
+        while let Some(v) = queue.pop_front() {
+            for (i, e) in self.adjs[v].iter().enumerate() {
+                if e.cap > 0 && parent[e.to].is_none() {
+                    parent[e.to] = Some((v, i));
+                    queue.push_back(e.to);
+                }
+            }
+        }
+        parent
+    }
+
+    /// augment along shortest paths until the sink is cut off, returns the
+    /// vertices on the source side of the minimum cut.
+    fn min_cut(&mut self) -> Vec<bool> {
+        loop {
+            let parent = self.search();
+            if parent[Self::SINK].is_none() {
+                return parent.iter().map(|p| p.is_some()).collect();
+            }
+            let mut v = Self::SINK;
+            while v != Self::SOURCE {
+                let (u, i) = parent[v].unwrap();
+                self.adjs[u][i].cap -= 1;
+                let rev = self.adjs[u][i].rev;
+                self.adjs[v][rev].cap += 1;
+                v = u;
+            }
+        }
+    }
+}
+
+fn vertex_in(id: AigNodeId) -> usize {
+    2 + 2 * id
+}
+
+fn vertex_out(id: AigNodeId) -> usize {
+    3 + 2 * id
+}
+
+impl Aig {
+    /// distance from the latch outputs of nodes only depending on latchs.
+    fn retime_distance(&self) -> Vec<Option<usize>> {
+        let mut distance = vec![None; self.num_nodes()];
+        distance[0] = Some(0);
+        for latch in &self.latchs {
+            distance[latch.input] = Some(0);
+        }
+        for node in self.ands_iter() {
+            let d0 = distance[node.fanin0().node_id()];
+            let d1 = distance[node.fanin1().node_id()];
+            if let (Some(d0), Some(d1)) = (d0, d1) {
+                distance[node.node_id()] = Some(d0.max(d1) + 1);
+            }
+        }
+        distance
+    }
+
+    /// the region of the minimum forward retiming whose nodes are at most
+    /// `limit` away from the latchs.
+    fn retime_region(&self, alive: &[bool], distance: &[Option<usize>], limit: usize) -> Vec<bool> {
+        let eligible: Vec<bool> = (0..self.num_nodes())
+            .map(|id| id > 0 && alive[id] && distance[id].is_some_and(|d| d <= limit))
+            .collect();
+        let mut graph = FlowGraph::new(vertex_in(self.num_nodes()));
+        for latch in &self.latchs {
+            graph.add_edge(FlowGraph::SOURCE, vertex_in(latch.input), RETIME_INFINITE);
+        }
+        for root in self.roots() {
+            if eligible[root.node_id()] {
+                graph.add_edge(vertex_out(root.node_id()), FlowGraph::SINK, RETIME_INFINITE);
+            }
+        }
+        for id in self.nodes_range() {
+            if !eligible[id] {
+                continue;
+            }
+            graph.add_edge(vertex_in(id), vertex_out(id), 1);
+            for fanout in &self.nodes[id].fanouts {
+                let fanout = fanout.node_id();
+                if !alive[fanout] {
+                    continue;
+                }
+                if eligible[fanout] {
+                    graph.add_edge(vertex_out(id), vertex_in(fanout), RETIME_INFINITE);
+                    graph.add_edge(vertex_in(fanout), vertex_in(id), RETIME_INFINITE);
+                } else {
+                    graph.add_edge(vertex_out(id), FlowGraph::SINK, RETIME_INFINITE);
+                }
+            }
+        }
+        let source_side = graph.min_cut();
+        (0..self.num_nodes())
+            .map(|id| eligible[id] && source_side[vertex_in(id)])
+            .collect()
+    }
+
+    /// nodes of the region that need a register after retiming.
+    fn retime_boundary(&self, alive: &[bool], region: &[bool]) -> Vec<AigNodeId> {
+        let mut boundary = vec![false; self.num_nodes()];
+        for root in self.roots() {
+            boundary[root.node_id()] = region[root.node_id()];
+        }
+        for id in self.nodes_range() {
+            if region[id] {
+                boundary[id] |= self.nodes[id]
+                    .fanouts
+                    .iter()
+                    .any(|f| alive[f.node_id()] && !region[f.node_id()]);
+            }
+        }
+        (0..self.num_nodes()).filter(|id| boundary[*id]).collect()
+    }
+
+    /// combinational depth of the design after retiming the region.
+    fn retime_depth(&self, region: &[bool], boundary: &[AigNodeId]) -> usize {
+        let mut level = vec![0; self.num_nodes()];
+        for node in self.ands_iter() {
+            if !region[node.node_id()] {
+                let fanin = |e: AigEdge| {
+                    if region[e.node_id()] {
+                        0
+                    } else {
+                        level[e.node_id()]
+                    }
+                };
+                level[node.node_id()] = fanin(node.fanin0()).max(fanin(node.fanin1())) + 1;
+            }
+        }
+        let mut depth = level.iter().copied().max().unwrap_or(0);
+        let mut copy = vec![0; self.num_nodes()];
+        for latch in &self.latchs {
+            if !region[latch.next.node_id()] {
+                copy[latch.input] = level[latch.next.node_id()];
+            }
+        }
+        for node in self.ands_iter() {
+            if region[node.node_id()] {
+                copy[node.node_id()] =
+                    copy[node.fanin0().node_id()].max(copy[node.fanin1().node_id()]) + 1;
+            }
+        }
+        for id in boundary {
+            depth = depth.max(copy[*id]);
+        }
+        depth
+    }
+
+    /// Forward retiming that moves the latchs over and nodes to minimize the
+    /// number of registers, keeping the combinational depth within
+    /// `max_depth` if given. Init values of the new registers are computed by
+    /// evaluating the moved logic on the original init state. The design is
+    /// left unchanged when retiming does not save registers. The FRAIG state
+    /// is dropped.
+    pub fn retime(&mut self, max_depth: Option<usize>) -> RetimeMap {
+        let unchanged = RetimeMap {
+            latchs: self.latchs.iter().map(|l| l.input).collect(),
+        };
+        let roots = self.roots();
+        let alive = self.fanin_logic_cone(&roots);
+        let distance = self.retime_distance();
+        let mut limit = distance.iter().flatten().copied().max().unwrap_or(0);
+        let (region, boundary) = loop {
+            let region = self.retime_region(&alive, &distance, limit);
+            let boundary = self.retime_boundary(&alive, &region);
+            if max_depth.is_none_or(|d| self.retime_depth(&region, &boundary) <= d) {
+                break (region, boundary);
+            }
+            if limit == 0 {
+                return unchanged;
+            }
+            limit -= 1;
+        };
+        if boundary.len() >= self.latchs.len() {
+            return unchanged;
+        }
+        self.fraig = None;
+        let init: Vec<AigEdge> = self
+            .latchs
+            .iter()
+            .map(|l| AigEdge::new(l.input, !l.init))
+            .collect();
+        let value = self.evaluate(&init);
+        let mut replace = vec![None; self.num_nodes()];
+        for id in &boundary {
+            let input = self.nodes.len();
+            self.nodes.push(AigNode::new_latch_input(input));
+            self.sat_solver.add_input_node(input);
+            replace[*id] = Some(AigEdge::from(input));
+        }
+        let resolve = |e: AigEdge| match replace[e.node_id()] {
+            Some(_) => map_edge(&replace, e),
+            None => e,
+        };
+        let mut copy = vec![None; replace.len()];
+        copy[0] = Some(AigEdge::constant_edge(false));
+        for latch in &self.latchs {
+            copy[latch.input] = Some(resolve(latch.next));
+        }
+        for id in 1..replace.len() {
+            if region[id] && self.nodes[id].is_and() {
+                let fanin0 = map_edge(&copy, self.nodes[id].fanin0());
+                let fanin1 = map_edge(&copy, self.nodes[id].fanin1());
+                copy[id] = Some(self.new_and_node(fanin0, fanin1));
+            }
+        }
+        self.latchs = boundary
+            .iter()
+            .map(|id| {
+                AigLatch::new(
+                    replace[*id].unwrap().node_id(),
+                    copy[*id].unwrap(),
+                    value[*id].unwrap(),
+                )
+            })
+            .collect();
+        self.symbols.latchs.clear();
+        self.rebuild(&replace);
+        RetimeMap { latchs: boundary }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Aig;
+
+    #[test]
+    fn test_retime() {
+        let mut aig = Aig::from_file("aigs/retime.aag").unwrap();
+        let map = aig.retime(None);
+        assert_eq!(map.latchs, [5]);
+        assert_eq!(aig.latchs.len(), 1);
+        assert!(aig.latchs[0].init);
+        assert_eq!(aig.num_ands, 1);
+        assert_eq!(aig.outputs[0].node_id(), aig.latchs[0].input);
+        assert_eq!(aig.symbols().input(1), Some("b"));
+        assert_eq!(aig.symbols().latch(0), None);
+    }
+}
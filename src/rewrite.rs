@@ -0,0 +1,542 @@
+use crate::{cut::Cut, mffc::resolve_edge, Aig, AigEdge, AigNodeId};
+use std::{collections::HashMap, mem::swap};
+
+const REWRITE_CUT_SIZE: usize = 4;
+
+const REWRITE_CUT_LIMIT: usize = 8;
+
+type Truth4 = u16;
+
+const TRUTH4_VARS: [Truth4; 4] = [0xAAAA, 0xCCCC, 0xF0F0, 0xFF00];
+
+const TRUTH4_TRUE: Truth4 = 0xFFFF;
+
+fn cofactor0(truth: Truth4, var: usize) -> Truth4 {
+    let t = truth & !TRUTH4_VARS[var];
+    t | (t << (1 << var))
+}
+
+fn cofactor1(truth: Truth4, var: usize) -> Truth4 {
+    let t = truth & TRUTH4_VARS[var];
+    t | (t >> (1 << var))
+}
+
+fn support(truth: Truth4) -> usize {
+    (0..4)
+        .filter(|v| cofactor0(truth, *v) != cofactor1(truth, *v))
+        .fold(0, |s, v| s | 1 << v)
+}
+
+fn exists(mut truth: Truth4, vars: usize) -> Truth4 {
+    for v in 0..4 {
+        if vars >> v & 1 == 1 {
+            truth = cofactor0(truth, v) | cofactor1(truth, v);
+        }
+    }
+    truth
+}
+
+/// `f(x) = out ^ c(z)` where `z_j = x_perm[j] ^ neg_j` and `c` is the class
+/// representative.
+#[derive(Debug, Clone, Copy)]
+struct NpnTransform {
+    perm: [usize; 4],
+    neg: usize,
+    out: bool,
+}
+
+impl NpnTransform {
+    fn apply(&self, truth: Truth4) -> Truth4 {
+        let mut ret = 0;
+        for z in 0..16 {
+            let mut x = 0;
+            for j in 0..4 {
+                if (z >> j & 1 == 1) ^ (self.neg >> j & 1 == 1) {
+                    x |= 1 << self.perm[j];
+                }
+            }
+            if (truth >> x & 1 == 1) ^ self.out {
+                ret |= 1 << z;
+            }
+        }
+        ret
+    }
+}
+
+fn permutations() -> Vec<[usize; 4]> {
+    let mut ret = Vec::new();
+    for a in 0..4 {
+        for b in 0..4 {
+            for c in 0..4 {
+                for d in 0..4 {
+                    let perm = [a, b, c, d];
+                    if (0..4).all(|i| perm.contains(&i)) {
+                        ret.push(perm);
+                    }
+                }
+            }
+        }
+    }
+    ret
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Decomposition {
+    Const(bool),
+    Literal(usize, bool),
+    And(Truth4, Truth4, bool),
+    Xor(usize, Truth4),
+    Mux(usize, Truth4, Truth4),
+}
+
+/// and nodes over local ids, 0 is the constant, 1..=4 are the leaves.
+#[derive(Debug, Clone)]
+struct Subgraph {
+    ands: Vec<(AigEdge, AigEdge)>,
+    root: AigEdge,
+}
+
+impl Subgraph {
+    const LEAVES_BEGIN: AigNodeId = 1;
+
+    const ANDS_BEGIN: AigNodeId = 5;
+
+    fn new_and_node(&mut self, mut fanin0: AigEdge, mut fanin1: AigEdge) -> AigEdge {
+        if fanin0.node_id() > fanin1.node_id() {
+            swap(&mut fanin0, &mut fanin1);
+        }
+        if fanin0 == AigEdge::constant_edge(false) || fanin0 == !fanin1 {
+            return AigEdge::constant_edge(false);
+        }
+        if fanin0 == AigEdge::constant_edge(true) {
+            return fanin1;
+        }
+        if fanin0 == fanin1 {
+            return fanin0;
+        }
+        match self.ands.iter().position(|and| *and == (fanin0, fanin1)) {
+            Some(idx) => AigEdge::new(Self::ANDS_BEGIN + idx, false),
+            None => {
+                self.ands.push((fanin0, fanin1));
+                AigEdge::new(Self::ANDS_BEGIN + self.ands.len() - 1, false)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RewriteLibrary {
+    canonical: HashMap<Truth4, (Truth4, NpnTransform)>,
+    decompositions: HashMap<Truth4, (usize, Decomposition)>,
+    subgraphs: HashMap<Truth4, Subgraph>,
+    permutations: Vec<[usize; 4]>,
+}
+
+impl RewriteLibrary {
+    fn new() -> Self {
+        Self {
+            permutations: permutations(),
+            ..Default::default()
+        }
+    }
+
+    fn canonicalize(&mut self, truth: Truth4) -> (Truth4, NpnTransform) {
+        if let Some(ret) = self.canonical.get(&truth) {
+            return *ret;
+        }
+        let mut best: Option<(Truth4, NpnTransform)> = None;
+        for perm in &self.permutations {
+            for neg in 0..16 {
+                for out in [false, true] {
+                    let transform = NpnTransform {
+                        perm: *perm,
+                        neg,
+                        out,
+                    };
+                    let canonical = transform.apply(truth);
+                    if best.is_none_or(|(b, _)| canonical < b) {
+                        best = Some((canonical, transform));
+                    }
+                }
+            }
+        }
+        let best = best.unwrap();
+        self.canonical.insert(truth, best);
+        best
+    }
+
+    fn decompose(&mut self, truth: Truth4) -> (usize, Decomposition) {
+        if let Some(ret) = self.decompositions.get(&truth) {
+            return *ret;
+        }
+        if truth == 0 || truth == TRUTH4_TRUE {
+            return (0, Decomposition::Const(truth != 0));
+        }
+        for (v, var) in TRUTH4_VARS.iter().enumerate() {
+            if truth == *var || truth == !*var {
+                return (0, Decomposition::Literal(v, truth != *var));
+            }
+        }
+        let supp = support(truth);
+        let mut best: Option<(usize, Decomposition)> = None;
+        let mut update = |cost: usize, decomposition: Decomposition| {
+            if best.is_none_or(|(c, _)| cost < c) {
+                best = Some((cost, decomposition));
+            }
+        };
+        let mut candidates = Vec::new();
+        for v in (0..4).filter(|v| supp >> v & 1 == 1) {
+            let f0 = cofactor0(truth, v);
+            let f1 = cofactor1(truth, v);
+            let x = TRUTH4_VARS[v];
+            if f0 == 0 {
+                candidates.push((1, Decomposition::And(x, f1, false)));
+            } else if f1 == 0 {
+                candidates.push((1, Decomposition::And(!x, f0, false)));
+            } else if f0 == TRUTH4_TRUE {
+                candidates.push((1, Decomposition::And(x, !f1, true)));
+            } else if f1 == TRUTH4_TRUE {
+                candidates.push((1, Decomposition::And(!x, !f0, true)));
+            } else if f0 == !f1 {
+                candidates.push((3, Decomposition::Xor(v, f0)));
+            } else {
+                candidates.push((3, Decomposition::Mux(v, f1, f0)));
+            }
+        }
+        for part in 1..supp {
+            let rest = supp & !part;
+            if part & supp != part || part > rest {
+                continue;
+            }
+            for (f, compl) in [(truth, false), (!truth, true)] {
+                let g = exists(f, rest);
+                let h = exists(f, part);
+                if g & h == f {
+                    candidates.push((1, Decomposition::And(g, h, compl)));
+                }
+            }
+        }
+        for (cost, decomposition) in candidates {
+            let cost = cost
+                + match decomposition {
+                    Decomposition::And(g, h, _) => self.decompose(g).0 + self.decompose(h).0,
+                    Decomposition::Xor(_, g) => self.decompose(g).0,
+                    Decomposition::Mux(_, f1, f0) => self.decompose(f1).0 + self.decompose(f0).0,
+                    _ => unreachable!(),
+                };
+            update(cost, decomposition);
+        }
+        let best = best.unwrap();
+        self.decompositions.insert(truth, best);
+        best
+    }
+
+    fn synthesize(&mut self, truth: Truth4, subgraph: &mut Subgraph) -> AigEdge {
+        let literal = |v: usize| AigEdge::new(Subgraph::LEAVES_BEGIN + v, false);
+        match self.decompose(truth).1 {
+            Decomposition::Const(c) => AigEdge::constant_edge(c),
+            Decomposition::Literal(v, compl) => AigEdge::new(Subgraph::LEAVES_BEGIN + v, compl),
+            Decomposition::And(g, h, compl) => {
+                let g = self.synthesize(g, subgraph);
+                let h = self.synthesize(h, subgraph);
+                let and = subgraph.new_and_node(g, h);
+                if compl {
+                    !and
+                } else {
+                    and
+                }
+            }
+            Decomposition::Xor(v, g) => {
+                let g = self.synthesize(g, subgraph);
+                let x0 = subgraph.new_and_node(literal(v), !g);
+                let x1 = subgraph.new_and_node(!literal(v), g);
+                !subgraph.new_and_node(!x0, !x1)
+            }
+            Decomposition::Mux(v, f1, f0) => {
+                let f1 = self.synthesize(f1, subgraph);
+                let f0 = self.synthesize(f0, subgraph);
+                let m1 = subgraph.new_and_node(literal(v), f1);
+                let m0 = subgraph.new_and_node(!literal(v), f0);
+                !subgraph.new_and_node(!m1, !m0)
+            }
+        }
+    }
+
+    fn subgraph(&mut self, truth: Truth4) -> (Subgraph, NpnTransform) {
+        let (canonical, transform) = self.canonicalize(truth);
+        if !self.subgraphs.contains_key(&canonical) {
+            let mut subgraph = Subgraph {
+                ands: Vec::new(),
+                root: AigEdge::constant_edge(false),
+            };
+            subgraph.root = self.synthesize(canonical, &mut subgraph);
+            self.subgraphs.insert(canonical, subgraph);
+        }
+        (self.subgraphs[&canonical].clone(), transform)
+    }
+}
+
+struct RewriteState {
+    num_old: usize,
+    refs: Vec<usize>,
+    dead: Vec<bool>,
+    replace: Vec<Option<AigEdge>>,
+    strash: HashMap<(AigEdge, AigEdge), AigNodeId>,
+}
+
+impl RewriteState {
+    fn resolve(&self, edge: AigEdge) -> AigEdge {
+        resolve_edge(&self.replace, edge)
+    }
+
+    fn lookup(&self, node: AigNodeId, mut fanin0: AigEdge, mut fanin1: AigEdge) -> Option<AigEdge> {
+        if fanin0.node_id() > fanin1.node_id() {
+            swap(&mut fanin0, &mut fanin1);
+        }
+        if fanin0 == AigEdge::constant_edge(false) || fanin0 == !fanin1 {
+            return Some(AigEdge::constant_edge(false));
+        }
+        if fanin0 == AigEdge::constant_edge(true) {
+            return Some(fanin1);
+        }
+        if fanin0 == fanin1 {
+            return Some(fanin0);
+        }
+        let id = *self.strash.get(&(fanin0, fanin1))?;
+        if (id < node || id >= self.num_old) && !self.dead[id] && self.refs[id] > 0 {
+            Some(id.into())
+        } else {
+            None
+        }
+    }
+}
+
+impl Aig {
+    fn rewrite_cut_valid(&self, state: &RewriteState, node: AigNodeId, cut: &Cut) -> bool {
+        if cut
+            .leaves()
+            .iter()
+            .any(|l| state.dead[*l] && state.replace[*l].is_none())
+        {
+            return false;
+        }
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if cut.leaves().contains(&id) {
+                continue;
+            }
+            if state.dead[id] {
+                return false;
+            }
+            stack.push(self.nodes[id].fanin0().node_id());
+            stack.push(self.nodes[id].fanin1().node_id());
+        }
+        true
+    }
+
+    fn rewrite_deref(
+        &self,
+        state: &mut RewriteState,
+        leaves: &[AigNodeId],
+        node: AigNodeId,
+        mffc: &mut Vec<AigNodeId>,
+    ) {
+        let replace = &state.replace;
+        let fanins = |id: AigNodeId| self.rewrite_fanins(replace, leaves, id);
+        self.deref_node_with(&mut state.refs, node, &fanins, mffc);
+    }
+
+    fn rewrite_ref(&self, state: &mut RewriteState, leaves: &[AigNodeId], node: AigNodeId) {
+        let replace = &state.replace;
+        let fanins = |id: AigNodeId| self.rewrite_fanins(replace, leaves, id);
+        self.ref_node_with(&mut state.refs, node, &fanins);
+    }
+
+    /// fanins of a node inside the cut, the leaves are resolved and never
+    /// expanded.
+    fn rewrite_fanins(
+        &self,
+        replace: &[Option<AigEdge>],
+        leaves: &[AigNodeId],
+        node: AigNodeId,
+    ) -> [(AigEdge, bool); 2] {
+        [self.nodes[node].fanin0(), self.nodes[node].fanin1()].map(|fanin| {
+            if leaves.contains(&fanin.node_id()) {
+                (resolve_edge(replace, fanin), false)
+            } else {
+                (fanin, true)
+            }
+        })
+    }
+
+    fn rewrite_leaves(
+        &self,
+        state: &RewriteState,
+        cut: &Cut,
+        transform: &NpnTransform,
+    ) -> Vec<AigEdge> {
+        let mut ret = vec![AigEdge::constant_edge(false)];
+        for j in 0..4 {
+            ret.push(match cut.leaves().get(transform.perm[j]) {
+                Some(leaf) => {
+                    let leaf = state.resolve((*leaf).into());
+                    if transform.neg >> j & 1 == 1 {
+                        !leaf
+                    } else {
+                        leaf
+                    }
+                }
+                None => AigEdge::constant_edge(false),
+            });
+        }
+        ret
+    }
+
+    fn rewrite_count_new(
+        &self,
+        state: &RewriteState,
+        node: AigNodeId,
+        subgraph: &Subgraph,
+        leaves: &[AigEdge],
+    ) -> usize {
+        let mut values: Vec<Option<AigEdge>> = leaves.iter().map(|l| Some(*l)).collect();
+        let mut count = 0;
+        for (fanin0, fanin1) in &subgraph.ands {
+            let value = match (values[fanin0.node_id()], values[fanin1.node_id()]) {
+                (Some(v0), Some(v1)) => state.lookup(
+                    node,
+                    if fanin0.compl() { !v0 } else { v0 },
+                    if fanin1.compl() { !v1 } else { v1 },
+                ),
+                _ => None,
+            };
+            if value.is_none() {
+                count += 1;
+            }
+            values.push(value);
+        }
+        count
+    }
+
+    fn rewrite_build(
+        &mut self,
+        state: &mut RewriteState,
+        node: AigNodeId,
+        subgraph: &Subgraph,
+        leaves: &[AigEdge],
+    ) -> AigEdge {
+        let mut values: Vec<AigEdge> = leaves.to_vec();
+        for (fanin0, fanin1) in &subgraph.ands {
+            let v0 = values[fanin0.node_id()];
+            let v1 = values[fanin1.node_id()];
+            let v0 = if fanin0.compl() { !v0 } else { v0 };
+            let v1 = if fanin1.compl() { !v1 } else { v1 };
+            let value = match state.lookup(node, v0, v1) {
+                Some(value) => value,
+                None => {
+                    let value = self.new_and_node(v0, v1);
+                    state.refs.resize(self.num_nodes(), 0);
+                    state.dead.resize(self.num_nodes(), false);
+                    let and = &self.nodes[value.node_id()];
+                    state.refs[and.fanin0().node_id()] += 1;
+                    state.refs[and.fanin1().node_id()] += 1;
+                    state
+                        .strash
+                        .insert((and.fanin0(), and.fanin1()), value.node_id());
+                    value
+                }
+            };
+            values.push(value);
+        }
+        let root = values[subgraph.root.node_id()];
+        if subgraph.root.compl() {
+            !root
+        } else {
+            root
+        }
+    }
+
+    /// DAG-aware rewriting of 4-input cuts with precomputed structures of
+    /// their NPN classes. The FRAIG state is dropped, run `fraig` again
+    /// afterwards if needed.
+    pub fn rewrite(&mut self) {
+        self.fraig = None;
+        let num_old = self.num_nodes();
+        let cuts = self.enumerate_cuts(REWRITE_CUT_SIZE, REWRITE_CUT_LIMIT);
+        let refs = self.reference_counts();
+        let mut strash = HashMap::new();
+        for and in self.ands_iter() {
+            strash
+                .entry((and.fanin0(), and.fanin1()))
+                .or_insert(and.node_id());
+        }
+        let mut state = RewriteState {
+            num_old,
+            refs,
+            dead: vec![false; num_old],
+            replace: vec![None; num_old],
+            strash,
+        };
+        let mut library = RewriteLibrary::new();
+        for (node, node_cuts) in cuts.iter().enumerate().take(num_old).skip(1) {
+            if !self.nodes[node].is_and() || state.dead[node] || state.refs[node] == 0 {
+                continue;
+            }
+            let mut best: Option<(usize, Subgraph, Vec<AigEdge>, &Cut)> = None;
+            for cut in &node_cuts[1..] {
+                if !self.rewrite_cut_valid(&state, node, cut) {
+                    continue;
+                }
+                let (mut subgraph, transform) = library.subgraph(cut.truth() as Truth4);
+                if transform.out {
+                    subgraph.root = !subgraph.root;
+                }
+                let leaves = self.rewrite_leaves(&state, cut, &transform);
+                let mut mffc = Vec::new();
+                self.rewrite_deref(&mut state, cut.leaves(), node, &mut mffc);
+                let added = self.rewrite_count_new(&state, node, &subgraph, &leaves);
+                self.rewrite_ref(&mut state, cut.leaves(), node);
+                if added < mffc.len() && best.as_ref().is_none_or(|b| mffc.len() - added > b.0) {
+                    best = Some((mffc.len() - added, subgraph, leaves, cut));
+                }
+            }
+            if let Some((_, subgraph, leaves, cut)) = best {
+                let mut mffc = Vec::new();
+                self.rewrite_deref(&mut state, cut.leaves(), node, &mut mffc);
+                for id in mffc {
+                    state.dead[id] = true;
+                }
+                let by = self.rewrite_build(&mut state, node, &subgraph, &leaves);
+                state.refs[by.node_id()] += state.refs[node];
+                state.refs[node] = 0;
+                state.replace[node] = Some(by);
+            }
+        }
+        self.rebuild(&state.replace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::random_output_values, Aig};
+
+    #[test]
+    fn test_rewrite_cec1() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        aig.rewrite();
+        assert_eq!(aig.num_ands, 0);
+        assert_eq!(aig.outputs[0], aig.outputs[1]);
+    }
+
+    #[test]
+    fn test_rewrite_equivalence() {
+        let origin = Aig::from_file("aigs/i10.aag").unwrap();
+        let mut aig = Aig::from_file("aigs/i10.aag").unwrap();
+        aig.rewrite();
+        assert!(aig.num_ands <= origin.num_ands);
+        assert_eq!(
+            random_output_values(&origin, 0, 64),
+            random_output_values(&aig, 0, 64)
+        );
+    }
+}
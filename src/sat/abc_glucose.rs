@@ -7,6 +7,10 @@ use abc_glucose::{Lit, Var};
 #[derive(Debug)]
 pub struct Solver {
     solver: abc_glucose::Solver,
+    /// the variable of every node
+    vars: Vec<Var>,
+    /// the node of every variable, `None` once the node is truncated
+    nodes: Vec<Option<AigNodeId>>,
     cex: Vec<AigEdge>,
     cex_all: Vec<Vec<AigEdge>>,
 }
@@ -17,6 +21,8 @@ impl Solver {
         assert_eq!(solver.add_var(), abc_glucose::Var::from(0));
         Self {
             solver,
+            vars: vec![Var::from(0)],
+            nodes: vec![Some(0)],
             cex: Vec::new(),
             cex_all: Vec::new(),
         }
@@ -24,30 +30,41 @@ impl Solver {
 }
 
 impl Solver {
-    fn node_to_var(node: AigNodeId) -> Var {
-        Var::from(node as i32)
+    fn new_var(&mut self, node: AigNodeId) -> Var {
+        assert_eq!(self.vars.len(), node);
+        let var = self.solver.add_var();
+        assert_eq!(i32::from(var) as usize, self.nodes.len());
+        self.vars.push(var);
+        self.nodes.push(Some(node));
+        var
+    }
+
+    fn edge_to_lit(&self, edge: AigEdge) -> Lit {
+        Lit::new(self.vars[edge.node_id()], edge.compl())
     }
 
-    fn edge_to_lit(edge: AigEdge) -> Lit {
-        Lit::new(Self::node_to_var(edge.node_id()), edge.compl())
+    fn lit_to_edge(nodes: &[Option<AigNodeId>], lit: Lit) -> Option<AigEdge> {
+        nodes[i32::from(lit.var()) as usize].map(|node| AigEdge::new(node, lit.compl()))
     }
 }
 
 impl SatSolver for Solver {
     fn add_input_node(&mut self, node: AigNodeId) {
-        let var = self.solver.add_var();
-        assert_eq!(var, Self::node_to_var(node));
+        self.new_var(node);
     }
 
     fn add_and_node(&mut self, node: AigNodeId, fanin0: AigEdge, fanin1: AigEdge) {
         assert!(fanin0.node_id() < fanin1.node_id());
         assert!(fanin1.node_id() < node);
-        let node = Self::node_to_var(node);
-        let var = self.solver.add_var();
-        assert_eq!(var, node);
-        assert_eq!(var, node);
-        self.solver
-            .set_fanin(node, Self::edge_to_lit(fanin0), Self::edge_to_lit(fanin1));
+        let var = self.new_var(node);
+        let (fanin0, fanin1) = (self.edge_to_lit(fanin0), self.edge_to_lit(fanin1));
+        self.solver.set_fanin(var, fanin0, fanin1);
+    }
+
+    fn truncate_nodes(&mut self, num_nodes: usize) {
+        for var in self.vars.drain(num_nodes..) {
+            self.nodes[i32::from(var) as usize] = None;
+        }
     }
 
     fn new_round(&mut self) {
@@ -56,7 +73,7 @@ impl SatSolver for Solver {
 
     fn mark_cone(&mut self, cones: &[AigEdge]) {
         for c in cones {
-            self.solver.mark_cone(Self::node_to_var(c.node_id()))
+            self.solver.mark_cone(self.vars[c.node_id()])
         }
     }
 
@@ -67,17 +84,14 @@ impl SatSolver for Solver {
         {
             return None;
         }
-        let assumptions: Vec<Lit> = assumptions
-            .iter()
-            .map(|e| Lit::new(Self::node_to_var(e.node_id()), e.compl()))
-            .collect();
+        let assumptions: Vec<Lit> = assumptions.iter().map(|e| self.edge_to_lit(*e)).collect();
 
         match self.solver.solve(&assumptions) {
             Some(cex) => {
                 let set: HashSet<AigEdge> = HashSet::from_iter(
                     cex.iter()
                         .chain(assumptions.iter())
-                        .map(|l| AigEdge::new((Into::<i32>::into(l.var())) as usize, l.compl()))
+                        .filter_map(|l| Self::lit_to_edge(&self.nodes, *l))
                         .filter(|e| e.node_id() > 0),
                 );
                 self.cex = Vec::from_iter(set);
@@ -97,11 +111,7 @@ impl Solver {
     }
 
     pub fn add_clause(&mut self, clause: &Clause) {
-        let clause: Vec<Lit> = clause
-            .lits
-            .iter()
-            .map(|e| Lit::new(Self::node_to_var(e.node_id()), e.compl()))
-            .collect();
+        let clause: Vec<Lit> = clause.lits.iter().map(|e| self.edge_to_lit(*e)).collect();
         self.solver.add_clause(&clause);
     }
 
@@ -114,10 +124,7 @@ impl Solver {
         {
             return None;
         }
-        let assumptions: Vec<Lit> = assumptions
-            .iter()
-            .map(|e| Lit::new(Self::node_to_var(e.node_id()), e.compl()))
-            .collect();
+        let assumptions: Vec<Lit> = assumptions.iter().map(|e| self.edge_to_lit(*e)).collect();
 
         match self.solver.solve_all(&assumptions) {
             Some(cexs) => {
@@ -126,7 +133,7 @@ impl Solver {
                     let set: HashSet<AigEdge> = HashSet::from_iter(
                         cex.iter()
                             .chain(assumptions.iter())
-                            .map(|l| AigEdge::new((Into::<i32>::into(l.var())) as usize, l.compl()))
+                            .filter_map(|l| Self::lit_to_edge(&self.nodes, *l))
                             .filter(|e| e.node_id() > 0),
                     );
                     let mut cex = Vec::from_iter(set);
@@ -157,4 +164,20 @@ mod tests {
         let ret = solver.solve_without_mark_cone(&[AigEdge::new(3, true)]);
         dbg!(ret);
     }
+
+    #[test]
+    fn test_truncate_nodes() {
+        let mut solver = Solver::new();
+        solver.add_input_node(1);
+        solver.add_input_node(2);
+        solver.add_and_node(3, 1.into(), 2.into());
+        solver.add_and_node(4, AigEdge::new(1, true), 3.into());
+        assert!(solver.solve(&[4.into()]).is_none());
+        solver.truncate_nodes(3);
+        solver.add_and_node(3, AigEdge::new(1, true), 2.into());
+        assert!(solver.solve(&[3.into(), 1.into()]).is_none());
+        let cex = solver.solve(&[3.into()]).unwrap();
+        assert!(cex.contains(&AigEdge::new(1, true)));
+        assert!(cex.iter().all(|e| e.node_id() <= 3));
+    }
 }
@@ -27,6 +27,10 @@ impl SatSolver for Solver {
         self.vars.push(node);
     }
 
+    fn truncate_nodes(&mut self, num_nodes: usize) {
+        self.vars.truncate(num_nodes);
+    }
+
     fn new_round(&mut self) {}
 
     fn mark_cone(&mut self, _cones: &[AigEdge]) {}
@@ -2,7 +2,7 @@ pub mod abc_glucose;
 pub mod glucose;
 pub mod minisat;
 
-use crate::{Aig, AigEdge, AigNodeId};
+use crate::{Aig, AigEdge, AigNode, AigNodeId};
 use std::{
     collections::HashMap,
     fmt::Debug,
@@ -15,6 +15,11 @@ pub trait SatSolver: Debug {
 
     fn add_and_node(&mut self, node: AigNodeId, fanin0: AigEdge, fanin1: AigEdge);
 
+    /// forget the nodes from `num_nodes` on, the nodes added next take their
+    /// ids. The solver keeps its state, the variables of the forgotten nodes
+    /// stay behind unreferenced.
+    fn truncate_nodes(&mut self, num_nodes: usize);
+
     fn new_round(&mut self);
 
     fn mark_cone(&mut self, cones: &[AigEdge]);
@@ -28,42 +33,73 @@ pub trait SatSolver: Debug {
     }
 
     fn equivalence_check(&mut self, x: AigEdge, y: AigEdge) -> Option<&[AigEdge]> {
+        self.constrained_equivalence_check(x, y, &[])
+    }
+
+    fn equivalence_check_xy_z(&mut self, x: AigEdge, y: AigEdge, z: AigEdge) -> Option<&[AigEdge]> {
+        self.constrained_equivalence_check_xy_z(x, y, z, &[])
+    }
+
+    /// `equivalence_check` over the assignments where every constraint edge
+    /// holds, the counterexample satisfies the constraints.
+    fn constrained_equivalence_check(
+        &mut self,
+        x: AigEdge,
+        y: AigEdge,
+        constraints: &[AigEdge],
+    ) -> Option<&[AigEdge]> {
         self.new_round();
         self.mark_cone(&[x, y]);
+        self.mark_cone(constraints);
         let m = metadata(self as *const Self);
         let fake: *mut Self = from_raw_parts_mut(self as *mut Self as *mut (), m);
-        if let Some(ret) = self.solve_without_mark_cone(&[x, !y]) {
+        let assumptions = |a: &[AigEdge]| [constraints, a].concat();
+        if let Some(ret) = self.solve_without_mark_cone(&assumptions(&[x, !y])) {
             return Some(ret);
         }
-        unsafe { fake.as_mut().unwrap() }.solve_without_mark_cone(&[!x, y])
+        unsafe { fake.as_mut().unwrap() }.solve_without_mark_cone(&assumptions(&[!x, y]))
     }
 
-    fn equivalence_check_xy_z(&mut self, x: AigEdge, y: AigEdge, z: AigEdge) -> Option<&[AigEdge]> {
+    fn constrained_equivalence_check_xy_z(
+        &mut self,
+        x: AigEdge,
+        y: AigEdge,
+        z: AigEdge,
+        constraints: &[AigEdge],
+    ) -> Option<&[AigEdge]> {
         self.new_round();
         self.mark_cone(&[x, y, z]);
+        self.mark_cone(constraints);
         let m = metadata(self as *const Self);
         let fake: *mut Self = from_raw_parts_mut(self as *mut Self as *mut (), m);
-        if let Some(ret) = self.solve_without_mark_cone(&[x, y, !z]) {
+        let assumptions = |a: &[AigEdge]| [constraints, a].concat();
+        if let Some(ret) = self.solve_without_mark_cone(&assumptions(&[x, y, !z])) {
             return Some(ret);
         }
-        if let Some(ret) = unsafe { fake.as_mut().unwrap() }.solve_without_mark_cone(&[!x, z]) {
+        if let Some(ret) =
+            unsafe { fake.as_mut().unwrap() }.solve_without_mark_cone(&assumptions(&[!x, z]))
+        {
             return Some(ret);
         }
-        unsafe { fake.as_mut().unwrap() }.solve_without_mark_cone(&[!y, z])
+        unsafe { fake.as_mut().unwrap() }.solve_without_mark_cone(&assumptions(&[!y, z]))
+    }
+}
+
+/// add every node but the constant to the solver, in order.
+pub(crate) fn load_nodes(solver: &mut dyn SatSolver, nodes: &[AigNode]) {
+    for node in &nodes[1..] {
+        if node.is_and() {
+            solver.add_and_node(node.node_id(), node.fanin0(), node.fanin1())
+        } else {
+            assert!(node.is_cinput());
+            solver.add_input_node(node.node_id())
+        }
     }
 }
 
 impl Aig {
     pub fn setup_sat_solver(&mut self) {
-        for i in self.nodes_range() {
-            if self.nodes[i].is_and() {
-                self.sat_solver
-                    .add_and_node(i, self.nodes[i].fanin0(), self.nodes[i].fanin1())
-            } else {
-                assert!(self.nodes[i].is_cinput());
-                self.sat_solver.add_input_node(i)
-            }
-        }
+        load_nodes(self.sat_solver.as_mut(), &self.nodes);
     }
 }
 
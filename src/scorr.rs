@@ -0,0 +1,309 @@
+use crate::{simulate::SimulationWord, Aig, AigEdge, AigNode, AigNodeId};
+use std::collections::HashMap;
+
+const SCORR_SIM_FRAMES: usize = 32;
+
+/// candidate classes, the first member is the representative and every
+/// member carries its phase relative to it.
+type ScorrClasses = Vec<Vec<(AigNodeId, bool)>>;
+
+fn scorr_split<K, F>(classes: ScorrClasses, key: F) -> ScorrClasses
+where
+    K: Eq + std::hash::Hash,
+    F: Fn(AigNodeId, bool) -> K,
+{
+    let mut ret = Vec::new();
+    for class in classes {
+        let mut groups: Vec<Vec<(AigNodeId, bool)>> = Vec::new();
+        let mut index: HashMap<K, usize> = HashMap::new();
+        for (id, phase) in class {
+            let group = *index.entry(key(id, phase)).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push((id, phase));
+        }
+        for group in groups {
+            if group.len() > 1 {
+                let base = group[0].1;
+                ret.push(group.iter().map(|(id, p)| (*id, p ^ base)).collect());
+            }
+        }
+    }
+    ret.sort();
+    ret
+}
+
+impl Aig {
+    fn scorr_simulate(&mut self) -> Vec<Vec<SimulationWord>> {
+        let mut rng = self.new_word_generator();
+        let mut sigs = vec![Vec::with_capacity(SCORR_SIM_FRAMES); self.num_nodes()];
+        let mut words = vec![0; self.num_nodes()];
+        let mut latch_words: Vec<SimulationWord> = self
+            .latchs
+            .iter()
+            .map(|l| if l.init { !0 } else { 0 })
+            .collect();
+        let edge_word = |words: &[SimulationWord], e: AigEdge| {
+            if e.compl() {
+                !words[e.node_id()]
+            } else {
+                words[e.node_id()]
+            }
+        };
+        for _ in 0..SCORR_SIM_FRAMES {
+            for (latch, word) in self.latchs.iter().zip(latch_words.iter()) {
+                words[latch.input] = *word;
+            }
+            for id in self.nodes_range_with_true() {
+                let node = &self.nodes[id];
+                if node.is_and() {
+                    words[id] = edge_word(&words, node.fanin0()) & edge_word(&words, node.fanin1());
+                } else if node.is_prime_input() {
+                    words[id] = rng.rand_word();
+                }
+                sigs[id].push(words[id]);
+            }
+            latch_words = self
+                .latchs
+                .iter()
+                .map(|l| edge_word(&words, l.next))
+                .collect();
+        }
+        sigs
+    }
+
+    /// group the constant, latch inputs and and nodes by their sequential
+    /// simulation signature up to complement.
+    fn scorr_classes(&mut self) -> ScorrClasses {
+        let sigs = self.scorr_simulate();
+        let mut classes = vec![(0..self.num_nodes())
+            .filter(|id| *id == 0 || !self.nodes[*id].is_prime_input())
+            .map(|id| (id, false))
+            .collect()];
+        classes = scorr_split(classes, |id, _| {
+            let phase = sigs[id][0] & 1 == 1;
+            let sig: Vec<SimulationWord> = sigs[id]
+                .iter()
+                .map(|w| if phase { !w } else { *w })
+                .collect();
+            sig
+        });
+        for class in classes.iter_mut() {
+            let base = sigs[class[0].0][0] & 1 == 1;
+            for (id, phase) in class.iter_mut() {
+                *phase = (sigs[*id][0] & 1 == 1) != base;
+            }
+        }
+        classes
+    }
+
+    fn scorr_new_frame_input(&mut self) -> AigEdge {
+        let id = self.nodes.len();
+        self.nodes.push(AigNode::new_prime_input(id));
+        self.sat_solver.add_input_node(id);
+        id.into()
+    }
+
+    /// copy the first `num_old` nodes into a new time frame with the given
+    /// latch values, prime inputs get fresh nodes unless `keep_inputs`.
+    fn scorr_unroll(
+        &mut self,
+        num_old: usize,
+        latch_values: &[AigEdge],
+        keep_inputs: bool,
+    ) -> Vec<AigEdge> {
+        let mut frame = vec![AigEdge::constant_edge(false); num_old];
+        for (latch, value) in self.latchs.iter().zip(latch_values.iter()) {
+            frame[latch.input] = *value;
+        }
+        for id in 1..num_old {
+            if self.nodes[id].is_and() {
+                let fanin0 = self.nodes[id].fanin0();
+                let fanin1 = self.nodes[id].fanin1();
+                let fanin0 = AigEdge::new(
+                    frame[fanin0.node_id()].node_id(),
+                    frame[fanin0.node_id()].compl() ^ fanin0.compl(),
+                );
+                let fanin1 = AigEdge::new(
+                    frame[fanin1.node_id()].node_id(),
+                    frame[fanin1.node_id()].compl() ^ fanin1.compl(),
+                );
+                frame[id] = self.new_and_node(fanin0, fanin1);
+            } else if self.nodes[id].is_prime_input() {
+                frame[id] = if keep_inputs {
+                    id.into()
+                } else {
+                    self.scorr_new_frame_input()
+                };
+            }
+        }
+        frame
+    }
+
+    fn scorr_next_frame(&mut self, num_old: usize, frame: &[AigEdge]) -> Vec<AigEdge> {
+        let latch_values: Vec<AigEdge> = self
+            .latchs
+            .iter()
+            .map(|l| {
+                let next = frame[l.next.node_id()];
+                AigEdge::new(next.node_id(), next.compl() ^ l.next.compl())
+            })
+            .collect();
+        self.scorr_unroll(num_old, &latch_values, false)
+    }
+
+    fn scorr_equal(
+        &mut self,
+        frame: &[AigEdge],
+        repr: AigNodeId,
+        (id, phase): (AigNodeId, bool),
+    ) -> AigEdge {
+        let repr = frame[repr];
+        self.new_equal_node(
+            frame[id],
+            AigEdge::new(repr.node_id(), repr.compl() ^ phase),
+        )
+    }
+
+    /// first member violating its class in the frame under the assumptions,
+    /// returns the values of all nodes in the counterexample. The equality of
+    /// each member is rolled back after its query.
+    fn scorr_find_cex(
+        &mut self,
+        classes: &ScorrClasses,
+        frame: &[AigEdge],
+        assumptions: &[AigEdge],
+    ) -> Option<Vec<Option<bool>>> {
+        let num_nodes = self.num_nodes();
+        for class in classes {
+            for member in &class[1..] {
+                let equal = self.scorr_equal(frame, class[0].0, *member);
+                let mut assumptions = assumptions.to_vec();
+                assumptions.push(!equal);
+                let cex = self.sat_solver.solve(&assumptions).map(|c| c.to_vec());
+                let value = cex.map(|cex| self.evaluate(&cex));
+                self.truncate_nodes(num_nodes);
+                if value.is_some() {
+                    return value;
+                }
+            }
+        }
+        None
+    }
+
+    /// Signal correspondence: merge latches and nodes that are equivalent in
+    /// every reachable state. Candidates come from sequential simulation from
+    /// the init state and are refined until they hold in the first `k` frames
+    /// and are `k`-inductive. Merged latches are removed. The FRAIG state is
+    /// dropped.
+    pub fn scorr(&mut self, k: usize) {
+        assert!(k > 0);
+        self.fraig = None;
+        let num_old = self.num_nodes();
+        let mut classes = self.scorr_classes();
+        let init: Vec<AigEdge> = self
+            .latchs
+            .iter()
+            .map(|l| AigEdge::constant_edge(l.init))
+            .collect();
+        let mut base = vec![self.scorr_unroll(num_old, &init, true)];
+        while base.len() < k {
+            let next = self.scorr_next_frame(num_old, base.last().unwrap());
+            base.push(next);
+        }
+        let mut step: Vec<Vec<AigEdge>> = vec![(0..num_old).map(AigEdge::from).collect()];
+        while step.len() <= k {
+            let next = self.scorr_next_frame(num_old, step.last().unwrap());
+            step.push(next);
+        }
+        let num_unrolled = self.num_nodes();
+        let mut f = 0;
+        while f < k {
+            let cex = self.scorr_find_cex(&classes, &base[f], &[]);
+            self.truncate_nodes(num_unrolled);
+            match cex {
+                Some(value) => {
+                    classes = scorr_split(classes, |id, phase| {
+                        base.iter()
+                            .map(|frame| {
+                                value[frame[id].node_id()].map(|v| v ^ frame[id].compl() ^ phase)
+                            })
+                            .collect::<Vec<Option<bool>>>()
+                    })
+                }
+                None => f += 1,
+            }
+        }
+        while !classes.is_empty() {
+            let mut equals = Vec::new();
+            for frame in &step[..k] {
+                for class in &classes {
+                    for member in &class[1..] {
+                        equals.push(self.scorr_equal(frame, class[0].0, *member));
+                    }
+                }
+            }
+            let assumption = self.new_and_nodes(equals);
+            let cex = self.scorr_find_cex(&classes, &step[k], &[assumption]);
+            self.truncate_nodes(num_unrolled);
+            match cex {
+                Some(value) => {
+                    let frame = &step[k];
+                    classes = scorr_split(classes, |id, phase| {
+                        value[frame[id].node_id()].map(|v| v ^ frame[id].compl() ^ phase)
+                    })
+                }
+                None => break,
+            }
+        }
+        let mut replace = vec![None; num_old];
+        for class in &classes {
+            for (id, phase) in &class[1..] {
+                replace[*id] = Some(AigEdge::new(class[0].0, *phase));
+            }
+        }
+        self.retain_latchs(|l| replace[l.input].is_none());
+        self.rebuild(&replace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Aig, AigEdge};
+
+    #[test]
+    fn test_scorr() {
+        let mut aig = Aig::from_file("aigs/scorr.aag").unwrap();
+        aig.scorr(1);
+        assert_eq!(aig.latchs.len(), 1);
+        assert_eq!(aig.num_ands, 0);
+        assert_eq!(aig.outputs[0], AigEdge::constant_edge(false));
+    }
+
+    #[test]
+    fn test_scorr_k2() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        let expect = aig.num_ands;
+        aig.scorr(2);
+        assert_eq!(aig.latchs.len(), 3);
+        assert_eq!(aig.num_ands, expect);
+        let mut aig = Aig::from_file("aigs/scorr.aag").unwrap();
+        aig.scorr(2);
+        assert_eq!(aig.latchs.len(), 1);
+        assert_eq!(aig.outputs[0], AigEdge::constant_edge(false));
+    }
+
+    #[test]
+    fn test_scorr_find_cex_rollback() {
+        let mut aig = Aig::from_file("aigs/scorr.aag").unwrap();
+        let classes = aig.scorr_classes();
+        assert!(!classes.is_empty());
+        let frame: Vec<AigEdge> = (0..aig.num_nodes()).map(AigEdge::from).collect();
+        let num_nodes = aig.num_nodes();
+        let cex = aig.scorr_find_cex(&classes, &frame, &[]);
+        assert_eq!(aig.num_nodes(), num_nodes);
+        assert_eq!(aig.scorr_find_cex(&classes, &frame, &[]), cex);
+        assert_eq!(aig.num_nodes(), num_nodes);
+    }
+}
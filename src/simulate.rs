@@ -1,11 +1,12 @@
-use crate::{Aig, AigEdge, AigNodeId};
-use rand::{rngs::ThreadRng, thread_rng, Rng};
+use crate::{Aig, AigEdge, AigLatch, AigNode, AigNodeId};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::{
     fmt::{Display, Formatter, Result},
     iter::repeat,
     mem::{replace, take},
-    ops::Index,
+    ops::{Index, Range},
     simd::Simd,
+    thread,
 };
 
 pub type SimulationWord = u64;
@@ -14,6 +15,9 @@ pub type SimdSimulationWord = Simd<SimulationWord, 64>;
 
 pub const SIMULATION_FALSE_WORD: SimulationWord = 0;
 
+/// distinct SAT patterns used to fill the patterns violating constraints.
+const CONSTRAINT_SAT_PATTERNS: usize = 64;
+
 pub type SimulationWordsHash = SimulationWord;
 
 // const HASH_MUL: SimulationWordsHash = 4294967311;
@@ -47,7 +51,7 @@ fn simd_hash_function(hash: &mut SimulationWordsHash, word: &SimdSimulationWord)
     simd_word = ((simd_word >> SIMD_16) ^ simd_word) * SIMD_MUL;
     simd_word = ((simd_word >> SIMD_16) ^ simd_word) + SIMD_ADD;
     for w in simd_word.as_array() {
-        *hash = *hash ^ (*w + (*hash << 6) + (*hash >> 2));
+        *hash = *hash ^ w.wrapping_add(*hash << 6).wrapping_add(*hash >> 2);
     }
 }
 
@@ -94,6 +98,16 @@ impl SimulationWords {
         ret
     }
 
+    fn from_words(words: &[SimulationWord]) -> Self {
+        let nsimd = words.len() / SimdSimulationWord::LANES;
+        let simd_words = words
+            .chunks_exact(SimdSimulationWord::LANES)
+            .map(SimdSimulationWord::from_slice)
+            .collect();
+        let remain_words = words[nsimd * SimdSimulationWord::LANES..].to_vec();
+        SimulationWords::new_with_simd_words(simd_words, remain_words)
+    }
+
     fn false_words(nword: usize) -> Self {
         let nsimd = nword / SimdSimulationWord::LANES;
         let nremain = nword % SimdSimulationWord::LANES;
@@ -122,8 +136,20 @@ impl SimulationWords {
         self.compl
     }
 
-    pub fn new(nword: usize) -> Self {
-        let mut gen = RandomWordGenerator::new();
+    /// whether the words are equal up to complement on the words both have,
+    /// the hash only tells they may be.
+    pub fn abs_equal(&self, other: &SimulationWords) -> bool {
+        if self.hash != other.hash && self.nword() == other.nword() {
+            return false;
+        }
+        let compl = self.compl ^ other.compl;
+        (0..self.nword().min(other.nword())).all(|i| {
+            let word = other[i];
+            self[i] == if compl { !word } else { word }
+        })
+    }
+
+    pub fn new(nword: usize, gen: &mut RandomWordGenerator) -> Self {
         let nsimd = nword / SimdSimulationWord::LANES;
         let nremain = nword % SimdSimulationWord::LANES;
         let simd_words = repeat(())
@@ -149,6 +175,15 @@ impl SimulationWords {
         simd_hash_function(&mut self.hash, &if self.compl { !word } else { word });
         self.simd_words.push(word);
     }
+
+    /// move the simd words of `other` after the words of `self`.
+    fn append(&mut self, other: SimulationWords) {
+        assert!(other.nword_remain == 0);
+        self.simd_words.reserve(other.simd_words.len());
+        for word in other.simd_words {
+            self.push_simd_word(word);
+        }
+    }
 }
 
 impl Display for SimulationWords {
@@ -179,13 +214,41 @@ impl Index<usize> for SimulationWords {
     }
 }
 
+pub const DEFAULT_RNG_SEED: u64 = 0;
+
+/// Simulation settings of a design, kept across the passes that drop the
+/// FRAIG state.
+#[derive(Debug, Clone, Copy)]
+pub struct SimulationOptions {
+    /// maximum number of words kept per node by FRAIG, `None` keeps all.
+    pub word_limit: Option<usize>,
+    /// worker threads of random and sequential simulation.
+    pub threads: usize,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            word_limit: None,
+            threads: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct RandomWordGenerator {
-    rng: ThreadRng,
+    rng: StdRng,
 }
 
 impl RandomWordGenerator {
-    pub fn new() -> Self {
-        Self { rng: thread_rng() }
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn rand_bool(&mut self) -> bool {
+        self.rng.gen()
     }
 
     pub fn rand_word(&mut self) -> SimulationWord {
@@ -300,17 +363,8 @@ impl Simulation {
 impl Simulation {
     pub fn cleanup_redundant(&mut self, node_map: &[Option<AigNodeId>]) {
         let old = replace(&mut self.simulations, Vec::with_capacity(node_map.len()));
-        for (id, mut old_sim) in old.into_iter().enumerate() {
+        for (id, old_sim) in old.into_iter().enumerate() {
             if let Some(dst) = node_map[id] {
-                let ncopy = (old_sim.simd_words.len() - 1) / 2;
-                // if ncopy > 0 {
-                //     for i in 0..ncopy {
-                //         old_sim.simd_words[1 + i] =
-                //             old_sim.simd_words[old_sim.simd_words.len() - ncopy + i];
-                //     }
-                //     unsafe { old_sim.simd_words.set_len(1 + ncopy) };
-                //     old_sim.calculate_hash();
-                // }
                 assert_eq!(dst, self.simulations.len());
                 self.simulations.push(old_sim);
             }
@@ -318,6 +372,25 @@ impl Simulation {
     }
 }
 
+impl Simulation {
+    /// keep only the words at the given increasing indices on every node.
+    pub fn retain_words(&mut self, keep: &[usize]) {
+        assert!(keep.len() >= SimdSimulationWord::LANES);
+        for sim in &mut self.simulations {
+            let words: Vec<SimulationWord> = keep.iter().map(|i| sim[*i]).collect();
+            *sim = SimulationWords::from_words(&words);
+        }
+    }
+
+    /// renumber the nodes, node i of the result is node `order[i]`.
+    pub fn reorder(&mut self, order: &[AigNodeId]) {
+        assert_eq!(order.len(), self.simulations.len());
+        let mut old: Vec<Option<SimulationWords>> =
+            take(&mut self.simulations).into_iter().map(Some).collect();
+        self.simulations = order.iter().map(|id| old[*id].take().unwrap()).collect();
+    }
+}
+
 impl Index<usize> for Simulation {
     type Output = SimulationWords;
 
@@ -326,8 +399,170 @@ impl Index<usize> for Simulation {
     }
 }
 
+/// concatenate the words of the parts node by node. The parts are moved, so
+/// every word is held once, and the node ranges are merged by up to
+/// `nthread` worker threads.
+fn merge_simulations(parts: Vec<Simulation>, nthread: usize) -> Simulation {
+    let num_nodes = parts[0].num_nodes();
+    let chunk = num_nodes.div_ceil(nthread);
+    let mut ranges: Vec<Vec<Vec<SimulationWords>>> =
+        (0..num_nodes.div_ceil(chunk)).map(|_| Vec::new()).collect();
+    for part in parts {
+        let mut simulations = part.simulations.into_iter();
+        for range in ranges.iter_mut() {
+            range.push(simulations.by_ref().take(chunk).collect());
+        }
+    }
+    let merged: Vec<Vec<SimulationWords>> = thread::scope(|scope| {
+        let handles: Vec<_> = ranges
+            .into_iter()
+            .map(|range| {
+                scope.spawn(move || {
+                    let mut parts = range.into_iter();
+                    let mut merged = parts.next().unwrap();
+                    for part in parts {
+                        for (sim, other) in merged.iter_mut().zip(part) {
+                            sim.append(other);
+                        }
+                    }
+                    merged
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    Simulation {
+        simulations: merged.into_iter().flatten().collect(),
+    }
+}
+
+/// simulate the nodes in order, `words(id)` gives the words of the inputs
+/// and latch inputs.
+pub(crate) fn simulate_nodes<F>(nodes: &[AigNode], nsimd_word: usize, mut words: F) -> Simulation
+where
+    F: FnMut(AigNodeId) -> Vec<SimulationWord>,
+{
+    let nword = nsimd_word * SimdSimulationWord::LANES;
+    let mut simulations = Simulation {
+        simulations: vec![SimulationWords::false_words(nword)],
+    };
+    for node in &nodes[1..] {
+        if node.is_and() {
+            let sim_and = simulations.sim_and(node.fanin0(), node.fanin1());
+            simulations.simulations.push(sim_and);
+        } else {
+            let words = words(node.node_id());
+            assert_eq!(words.len(), nword);
+            simulations
+                .simulations
+                .push(SimulationWords::from_words(&words));
+        }
+    }
+    simulations
+}
+
 impl Aig {
-    pub fn new_simulation(&self, nsimd_word: usize) -> Simulation {
+    /// Seed the generator behind every random choice of simulation and
+    /// FRAIG, the same seed gives the same results.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// an independent generator drawn from the generator of the design.
+    pub(crate) fn new_word_generator(&mut self) -> RandomWordGenerator {
+        RandomWordGenerator::new(self.rng.gen())
+    }
+
+    /// Simulate with up to `nthread` worker threads from now on, in random,
+    /// constrained, FRAIG and sequential simulation. The threads only split
+    /// the words, the results are the same as with a single thread.
+    pub fn set_simulation_threads(&mut self, nthread: usize) {
+        assert!(nthread > 0);
+        self.simulation_options.threads = nthread;
+    }
+
+    pub fn simulation_threads(&self) -> usize {
+        self.simulation_options.threads
+    }
+
+    /// Simulation of `nsimd_word` simd words where `words(id)` gives the
+    /// words of every input and latch input node.
+    pub fn simulation_from_words<F>(&self, nsimd_word: usize, words: F) -> Simulation
+    where
+        F: FnMut(AigNodeId) -> Vec<SimulationWord>,
+    {
+        let nthread = self.simulation_options.threads;
+        if nthread > 1 && nsimd_word > 1 {
+            return self.simulation_from_words_parallel(nsimd_word, nthread, words);
+        }
+        simulate_nodes(&self.nodes, nsimd_word, words)
+    }
+
+    /// Same as `simulation_from_words` with the simd words split across up to
+    /// `nthread` worker threads.
+    pub fn simulation_from_words_parallel<F>(
+        &self,
+        nsimd_word: usize,
+        nthread: usize,
+        mut words: F,
+    ) -> Simulation
+    where
+        F: FnMut(AigNodeId) -> Vec<SimulationWord>,
+    {
+        let nword = nsimd_word * SimdSimulationWord::LANES;
+        let input_words: Vec<Vec<SimulationWord>> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                if node.is_cinput() {
+                    let words = words(node.node_id());
+                    assert_eq!(words.len(), nword);
+                    words
+                } else {
+                    Vec::new()
+                }
+            })
+            .collect();
+        assert!(nsimd_word > 0, "simulation needs at least one simd word");
+        let nthread = nthread.clamp(1, nsimd_word);
+        let chunk = nsimd_word.div_ceil(nthread);
+        let nodes = &self.nodes;
+        let input_words = &input_words;
+        let parts: Vec<Simulation> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..nsimd_word)
+                .step_by(chunk)
+                .map(|start| {
+                    let end = (start + chunk).min(nsimd_word);
+                    let range = start * SimdSimulationWord::LANES..end * SimdSimulationWord::LANES;
+                    scope.spawn(move || {
+                        simulate_nodes(nodes, end - start, |id| {
+                            input_words[id][range.clone()].to_vec()
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        merge_simulations(parts, nthread)
+    }
+
+    /// Same as `new_simulation` with the simd words split across up to
+    /// `nthread` worker threads, the random words are drawn in the same order.
+    pub fn new_simulation_parallel(&mut self, nsimd_word: usize, nthread: usize) -> Simulation {
+        let mut gen = self.new_word_generator();
+        let nwords = nsimd_word * SimdSimulationWord::LANES;
+        self.simulation_from_words_parallel(nsimd_word, nthread, |_| {
+            let sim = SimulationWords::new(nwords, &mut gen);
+            (0..nwords).map(|i| sim[i]).collect()
+        })
+    }
+
+    pub fn new_simulation(&mut self, nsimd_word: usize) -> Simulation {
+        let nthread = self.simulation_options.threads;
+        if nthread > 1 && nsimd_word > 1 {
+            return self.new_simulation_parallel(nsimd_word, nthread);
+        }
+        let mut gen = self.new_word_generator();
         let nwords = nsimd_word * SimdSimulationWord::LANES;
         let mut simulations = Simulation {
             simulations: vec![SimulationWords::false_words(nwords)],
@@ -337,24 +572,384 @@ impl Aig {
                 let sim_and = simulations.sim_and(node.fanin0(), node.fanin1());
                 simulations.simulations.push(sim_and);
             } else {
-                simulations.simulations.push(SimulationWords::new(nwords));
+                simulations
+                    .simulations
+                    .push(SimulationWords::new(nwords, &mut gen));
             }
         }
         simulations
     }
 }
 
+impl Aig {
+    /// values of the inputs in the cone of the constraints satisfying them,
+    /// keeping as many of the preferred values as a few SAT calls find.
+    fn constrained_pattern(
+        &mut self,
+        constraints: &[AigEdge],
+        prefer: &[AigEdge],
+    ) -> Option<Vec<AigEdge>> {
+        let mut k = prefer.len();
+        loop {
+            let mut assumptions = constraints.to_vec();
+            assumptions.extend(&prefer[..k]);
+            if let Some(cex) = self.sat_solver.solve(&assumptions) {
+                return Some(
+                    cex.iter()
+                        .copied()
+                        .filter(|e| self.nodes[e.node_id()].is_cinput())
+                        .collect(),
+                );
+            }
+            if k == 0 {
+                return None;
+            }
+            k /= 2;
+        }
+    }
+
+    /// Random simulation where the patterns violating any of the constraint
+    /// edges are masked and refilled with patterns found by SAT, so every
+    /// pattern satisfies the constraints. Panics if they are unsatisfiable.
+    pub fn new_constrained_simulation(
+        &mut self,
+        nsimd_word: usize,
+        constraints: &[AigEdge],
+    ) -> Simulation {
+        let simulation = self.new_simulation(nsimd_word);
+        let constraints: Vec<AigEdge> = constraints
+            .iter()
+            .copied()
+            .filter(|c| *c != AigEdge::constant_edge(true))
+            .collect();
+        if constraints.is_empty() {
+            return simulation;
+        }
+        let nword = simulation.nword();
+        let cone = self.fanin_logic_cone(&constraints);
+        let cone_inputs: Vec<AigNodeId> = self
+            .nodes_range()
+            .filter(|id| cone[*id] && self.nodes[*id].is_cinput())
+            .collect();
+        let mut words: Vec<Vec<SimulationWord>> = self
+            .nodes_range_with_true()
+            .map(|id| (0..nword).map(|i| simulation[id][i]).collect())
+            .collect();
+        let mut patterns: Vec<Vec<AigEdge>> = Vec::new();
+        let mut nfilled = 0;
+        for i in 0..nword {
+            let care = constraints.iter().fold(!0, |care, c| {
+                let word = simulation[c.node_id()][i];
+                care & if c.compl() { !word } else { word }
+            });
+            for bit in 0..SimulationWord::BITS {
+                if care >> bit & 1 == 1 {
+                    continue;
+                }
+                if patterns.len() < CONSTRAINT_SAT_PATTERNS {
+                    let prefer: Vec<AigEdge> = cone_inputs
+                        .iter()
+                        .map(|id| AigEdge::new(*id, words[*id][i] >> bit & 1 == 0))
+                        .collect();
+                    let pattern = self
+                        .constrained_pattern(&constraints, &prefer)
+                        .expect("constraints are unsatisfiable");
+                    patterns.push(pattern);
+                }
+                for lit in &patterns[nfilled % patterns.len()] {
+                    if lit.compl() {
+                        words[lit.node_id()][i] &= !(1 << bit);
+                    } else {
+                        words[lit.node_id()][i] |= 1 << bit;
+                    }
+                }
+                nfilled += 1;
+            }
+        }
+        self.simulation_from_words(nsimd_word, |id| take(&mut words[id]))
+    }
+}
+
+/// A failing trace of sequential simulation, the values of the inputs in
+/// every cycle from the init state until bad state `bad` holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationCex {
+    pub bad: usize,
+    pub inputs: Vec<Vec<bool>>,
+}
+
+/// the part of a design read by sequential simulation, shared by the worker
+/// threads.
+#[derive(Clone, Copy)]
+struct TraceDesign<'a> {
+    nodes: &'a [AigNode],
+    inputs: &'a [AigNodeId],
+    latchs: &'a [AigLatch],
+    bads: &'a [AigEdge],
+}
+
+impl TraceDesign<'_> {
+    /// the words of `nword` traces in the init state, node by node.
+    fn init_words(&self, nword: usize) -> Vec<SimulationWord> {
+        let mut words = vec![SIMULATION_FALSE_WORD; self.nodes.len() * nword];
+        for latch in self.latchs {
+            let init = if latch.init { !0 } else { 0 };
+            words[latch.input * nword..(latch.input + 1) * nword].fill(init);
+        }
+        words
+    }
+
+    /// simulate one cycle of the traces `range` out of `ninput_word` traces
+    /// per input word, `words` holds the range only. Returns the first bad
+    /// state, trace and bit failing, then moves the latches to the next state.
+    fn cycle(
+        &self,
+        words: &mut [SimulationWord],
+        inputs: &[SimulationWord],
+        ninput_word: usize,
+        range: Range<usize>,
+    ) -> Option<(usize, usize, u32)> {
+        let nword = range.len();
+        let edge_word = |words: &[SimulationWord], e: AigEdge, i: usize| {
+            let word = words[e.node_id() * nword + i];
+            if e.compl() {
+                !word
+            } else {
+                word
+            }
+        };
+        for (k, input) in self.inputs.iter().enumerate() {
+            words[input * nword..(input + 1) * nword].copy_from_slice(
+                &inputs[k * ninput_word + range.start..k * ninput_word + range.end],
+            );
+        }
+        for node in self.nodes.iter().filter(|n| n.is_and()) {
+            for i in 0..nword {
+                words[node.node_id() * nword + i] =
+                    edge_word(words, node.fanin0(), i) & edge_word(words, node.fanin1(), i);
+            }
+        }
+        for (b, bad) in self.bads.iter().enumerate() {
+            for i in 0..nword {
+                let word = edge_word(words, *bad, i);
+                if word != 0 {
+                    return Some((b, range.start + i, word.trailing_zeros()));
+                }
+            }
+        }
+        let next: Vec<SimulationWord> = self
+            .latchs
+            .iter()
+            .flat_map(|l| (0..nword).map(move |i| (l.next, i)))
+            .map(|(next, i)| edge_word(words, next, i))
+            .collect();
+        for (k, latch) in self.latchs.iter().enumerate() {
+            words[latch.input * nword..(latch.input + 1) * nword]
+                .copy_from_slice(&next[k * nword..(k + 1) * nword]);
+        }
+        None
+    }
+}
+
+impl Aig {
+    /// Sequential random simulation of `64 * nword` parallel traces from the
+    /// init state for at most `cycles` cycles. The bad states are checked in
+    /// every cycle and the first failing trace is returned. The traces are
+    /// split across the simulation threads, the inputs are drawn in the same
+    /// order so the result does not depend on the number of threads.
+    pub fn sequential_simulate(&mut self, nword: usize, cycles: usize) -> Option<SimulationCex> {
+        let mut rng = self.new_word_generator();
+        let design = TraceDesign {
+            nodes: &self.nodes,
+            inputs: &self.inputs,
+            latchs: &self.latchs,
+            bads: &self.bads,
+        };
+        let nthread = self.simulation_options.threads.clamp(1, nword.max(1));
+        let chunk = nword.div_ceil(nthread).max(1);
+        let ranges: Vec<Range<usize>> = (0..nword)
+            .step_by(chunk)
+            .map(|start| start..(start + chunk).min(nword))
+            .collect();
+        let mut parts: Vec<Vec<SimulationWord>> =
+            ranges.iter().map(|r| design.init_words(r.len())).collect();
+        let mut history: Vec<Vec<SimulationWord>> = Vec::new();
+        for _ in 0..cycles {
+            let inputs: Vec<SimulationWord> = (0..self.inputs.len() * nword)
+                .map(|_| rng.rand_word())
+                .collect();
+            let failing: Vec<Option<(usize, usize, u32)>> = if parts.len() > 1 {
+                let inputs = &inputs;
+                thread::scope(|scope| {
+                    let handles: Vec<_> = parts
+                        .iter_mut()
+                        .zip(ranges.iter().cloned())
+                        .map(|(words, range)| {
+                            scope.spawn(move || design.cycle(words, inputs, nword, range))
+                        })
+                        .collect();
+                    handles.into_iter().map(|h| h.join().unwrap()).collect()
+                })
+            } else {
+                parts
+                    .iter_mut()
+                    .zip(ranges.iter().cloned())
+                    .map(|(words, range)| design.cycle(words, &inputs, nword, range))
+                    .collect()
+            };
+            history.push(inputs);
+            if let Some((bad, i, bit)) = failing.into_iter().flatten().min() {
+                let inputs = history
+                    .iter()
+                    .map(|frame| {
+                        (0..self.inputs.len())
+                            .map(|k| frame[k * nword + i] >> bit & 1 == 1)
+                            .collect()
+                    })
+                    .collect();
+                return Some(SimulationCex { bad, inputs });
+            }
+        }
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Aig;
+    use super::{SimdSimulationWord, SimulationWord};
+    use crate::{Aig, AigEdge};
+    use std::collections::HashSet;
 
     #[test]
     fn test_simulation() {
-        let aig = Aig::from_file("aigs/counter-2bit.aag").unwrap();
+        let mut aig = Aig::from_file("aigs/counter-2bit.aag").unwrap();
         println!("{}", aig);
         let sim = aig.new_simulation(2);
         for s in &sim.simulations {
             println!("{:} {}", s, s.abs_hash_value());
         }
     }
+
+    #[test]
+    fn test_rng_seed() {
+        let mut x = Aig::from_file("aigs/cec1.aag").unwrap();
+        let mut y = Aig::from_file("aigs/cec1.aag").unwrap();
+        x.set_rng_seed(7);
+        y.set_rng_seed(7);
+        let sx = x.new_simulation(1);
+        let sy = y.new_simulation(1);
+        for id in x.nodes_range() {
+            assert_eq!(sx[id].abs_hash_value(), sy[id].abs_hash_value());
+        }
+        y.set_rng_seed(8);
+        let sy = y.new_simulation(1);
+        assert_ne!(sx[1].abs_hash_value(), sy[1].abs_hash_value());
+    }
+
+    #[test]
+    fn test_abs_equal() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let inputs = aig.inputs.clone();
+        let (a, b, c): (AigEdge, AigEdge, AigEdge) =
+            (inputs[0].into(), inputs[1].into(), inputs[2].into());
+        let ab = aig.new_and_node(a, b);
+        let x = aig.new_and_node(ab, c);
+        let bc = aig.new_and_node(b, c);
+        let y = !aig.new_and_node(a, bc);
+        assert_ne!(x.node_id(), y.node_id());
+        let simulation = aig.new_simulation(1);
+        assert!(simulation[x.node_id()].abs_equal(&simulation[y.node_id()]));
+        assert!(!simulation[x.node_id()].abs_equal(&simulation[inputs[0]]));
+    }
+
+    #[test]
+    fn test_parallel_simulation() {
+        let mut aig = Aig::from_file("aigs/i10.aag").unwrap();
+        aig.set_rng_seed(1);
+        let serial = aig.new_simulation(5);
+        aig.set_rng_seed(1);
+        let parallel = aig.new_simulation_parallel(5, 3);
+        assert_eq!(parallel.nword(), serial.nword());
+        for id in aig.nodes_range_with_true() {
+            assert_eq!(parallel[id].abs_hash_value(), serial[id].abs_hash_value());
+            assert_eq!(parallel[id].compl(), serial[id].compl());
+            for i in 0..serial.nword() {
+                assert_eq!(parallel[id][i], serial[id][i]);
+            }
+        }
+        aig.set_rng_seed(1);
+        aig.set_simulation_threads(8);
+        let parallel = aig.new_simulation(5);
+        for id in aig.nodes_range_with_true() {
+            assert_eq!(parallel[id].abs_hash_value(), serial[id].abs_hash_value());
+        }
+        aig.set_rng_seed(1);
+        let serial = aig.new_simulation_parallel(1, 1);
+        aig.set_rng_seed(1);
+        let parallel = aig.new_simulation_parallel(1, 4);
+        assert_eq!(parallel[1].abs_hash_value(), serial[1].abs_hash_value());
+    }
+
+    #[test]
+    fn test_constrained_simulation() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let inputs = aig.inputs.clone();
+        let equal = aig.new_equal_node(inputs[0].into(), inputs[2].into());
+        let constraint = aig.new_or_node(equal, inputs[1].into());
+        let simulation = aig.new_constrained_simulation(1, &[constraint]);
+        let mut values = HashSet::new();
+        for i in 0..simulation.nword() {
+            let word = simulation[constraint.node_id()][i];
+            assert_eq!(if constraint.compl() { !word } else { word }, !0);
+            for bit in 0..64 {
+                values.insert(
+                    inputs
+                        .iter()
+                        .map(|id| simulation[*id][i] >> bit & 1)
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+        assert_eq!(values.len(), 6);
+    }
+
+    #[test]
+    fn test_sequential_simulate() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        assert!(aig.sequential_simulate(1, 7).is_none());
+        let cex = aig.sequential_simulate(4, 64).unwrap();
+        assert_eq!(cex.bad, 0);
+        let (last, prefix) = cex.inputs.split_last().unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(prefix.iter().filter(|frame| frame[0]).count(), 7);
+        assert!(prefix.last().unwrap()[0]);
+    }
+
+    #[test]
+    fn test_merge_simulations() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        let mut words = |id: usize| -> Vec<SimulationWord> {
+            (0..3 * SimdSimulationWord::LANES)
+                .map(|i| (id as SimulationWord + 1).wrapping_mul(!0 / 3 + i as SimulationWord))
+                .collect()
+        };
+        let serial = aig.simulation_from_words(3, &mut words);
+        let merged = aig.simulation_from_words_parallel(3, 3, &mut words);
+        for id in aig.nodes_range_with_true() {
+            assert_eq!(merged[id].abs_hash_value(), serial[id].abs_hash_value());
+        }
+        aig.set_simulation_threads(2);
+        assert_eq!(aig.new_simulation(2).nword(), 2 * SimdSimulationWord::LANES);
+    }
+
+    #[test]
+    fn test_parallel_sequential_simulate() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.set_rng_seed(3);
+        let serial = aig.sequential_simulate(5, 64);
+        aig.set_rng_seed(3);
+        aig.set_simulation_threads(3);
+        assert_eq!(aig.sequential_simulate(5, 64), serial);
+        assert!(serial.is_some());
+    }
 }
@@ -0,0 +1,206 @@
+use crate::{Aig, AigNodeId};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result},
+};
+
+/// Structural statistics of a design. Histograms map a value to the number
+/// of nodes or latchs having it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AigStats {
+    pub num_inputs: usize,
+    pub num_latchs: usize,
+    pub num_outputs: usize,
+    pub num_bads: usize,
+    pub num_ands: usize,
+    /// maximum level of the and nodes.
+    pub depth: usize,
+    /// number of and nodes on each level, index 0 is unused.
+    pub levels: Vec<usize>,
+    /// fanout count of the inputs, latch inputs and and nodes.
+    pub fanouts: BTreeMap<usize, usize>,
+    /// size of the maximum fanout free cone of the and nodes.
+    pub mffcs: BTreeMap<usize, usize>,
+    /// number of inputs and latch inputs in the cone of each latch next.
+    pub latch_supports: BTreeMap<usize, usize>,
+    /// and nodes rooting a two level xor or xnor.
+    pub num_xors: usize,
+    /// and nodes rooting a two level multiplexer that is not a xor.
+    pub num_muxs: usize,
+    /// number of and nodes in the combinational cone of each bad state.
+    pub bad_cones: Vec<usize>,
+}
+
+fn histogram_json(histogram: &BTreeMap<usize, usize>) -> String {
+    let items: Vec<String> = histogram
+        .iter()
+        .map(|(k, v)| format!("\"{}\": {}", k, v))
+        .collect();
+    format!("{{{}}}", items.join(", "))
+}
+
+fn list_json(list: &[usize]) -> String {
+    let items: Vec<String> = list.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+impl AigStats {
+    pub fn to_json(&self) -> String {
+        let fields = [
+            ("inputs", self.num_inputs.to_string()),
+            ("latchs", self.num_latchs.to_string()),
+            ("outputs", self.num_outputs.to_string()),
+            ("bads", self.num_bads.to_string()),
+            ("ands", self.num_ands.to_string()),
+            ("depth", self.depth.to_string()),
+            ("levels", list_json(&self.levels)),
+            ("fanouts", histogram_json(&self.fanouts)),
+            ("mffcs", histogram_json(&self.mffcs)),
+            ("latch_supports", histogram_json(&self.latch_supports)),
+            ("xors", self.num_xors.to_string()),
+            ("muxs", self.num_muxs.to_string()),
+            ("bad_cones", list_json(&self.bad_cones)),
+        ];
+        let items: Vec<String> = fields
+            .iter()
+            .map(|(k, v)| format!("\"{}\": {}", k, v))
+            .collect();
+        format!("{{{}}}", items.join(", "))
+    }
+}
+
+impl Display for AigStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let histogram = |f: &mut Formatter<'_>, name: &str, histogram: &BTreeMap<usize, usize>| {
+            write!(f, "{}:", name)?;
+            for (k, v) in histogram {
+                write!(f, " {}x{}", k, v)?;
+            }
+            writeln!(f)
+        };
+        writeln!(f, "input num: {}", self.num_inputs)?;
+        writeln!(f, "latch num: {}", self.num_latchs)?;
+        writeln!(f, "output num: {}", self.num_outputs)?;
+        writeln!(f, "bad state num: {}", self.num_bads)?;
+        writeln!(f, "and num: {}", self.num_ands)?;
+        writeln!(f, "depth: {}", self.depth)?;
+        write!(f, "levels:")?;
+        for (level, num) in self.levels.iter().enumerate().skip(1) {
+            write!(f, " {}x{}", level, num)?;
+        }
+        writeln!(f)?;
+        histogram(f, "fanouts", &self.fanouts)?;
+        histogram(f, "mffcs", &self.mffcs)?;
+        histogram(f, "latch supports", &self.latch_supports)?;
+        writeln!(f, "xor num: {}", self.num_xors)?;
+        writeln!(f, "mux num: {}", self.num_muxs)?;
+        write!(f, "bad cones:")?;
+        for size in &self.bad_cones {
+            write!(f, " {}", size)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl Aig {
+    /// Some(true) for a xor, Some(false) for another multiplexer.
+    fn stats_mux_type(&self, id: AigNodeId) -> Option<bool> {
+        let node = &self.nodes[id];
+        if !node.is_and() || !node.fanin0().compl() || !node.fanin1().compl() {
+            return None;
+        }
+        let node0 = &self.nodes[node.fanin0().node_id()];
+        let node1 = &self.nodes[node.fanin1().node_id()];
+        if !node0.is_and() || !node1.is_and() {
+            return None;
+        }
+        let fanins0 = [node0.fanin0(), node0.fanin1()];
+        let fanins1 = [node1.fanin0(), node1.fanin1()];
+        let opposite = |i: usize, j: usize| fanins0[i] == !fanins1[j];
+        for i in 0..2 {
+            for j in 0..2 {
+                if opposite(i, j) {
+                    return Some(opposite(1 - i, 1 - j));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn stats(&self) -> AigStats {
+        let mut stats = AigStats {
+            num_inputs: self.inputs.len(),
+            num_latchs: self.latchs.len(),
+            num_outputs: self.outputs.len(),
+            num_bads: self.bads.len(),
+            num_ands: self.num_ands,
+            depth: 0,
+            levels: Vec::new(),
+            fanouts: BTreeMap::new(),
+            mffcs: BTreeMap::new(),
+            latch_supports: BTreeMap::new(),
+            num_xors: 0,
+            num_muxs: 0,
+            bad_cones: Vec::new(),
+        };
+        let mut refs = self.reference_counts();
+        for id in self.nodes_range() {
+            let node = &self.nodes[id];
+            *stats.fanouts.entry(node.fanouts.len()).or_default() += 1;
+            if !node.is_and() {
+                continue;
+            }
+            stats.depth = stats.depth.max(node.level);
+            if stats.levels.len() <= node.level {
+                stats.levels.resize(node.level + 1, 0);
+            }
+            stats.levels[node.level] += 1;
+            let mffc = self.deref_node(&mut refs, id).len();
+            self.ref_node(&mut refs, id);
+            *stats.mffcs.entry(mffc).or_default() += 1;
+            match self.stats_mux_type(id) {
+                Some(true) => stats.num_xors += 1,
+                Some(false) => stats.num_muxs += 1,
+                None => (),
+            }
+        }
+        for latch in &self.latchs {
+            let cone = self.fanin_logic_cone(&[latch.next]);
+            let support = self
+                .nodes_range()
+                .filter(|id| cone[*id] && self.nodes[*id].is_cinput())
+                .count();
+            *stats.latch_supports.entry(support).or_default() += 1;
+        }
+        for bad in &self.bads {
+            let cone = self.fanin_logic_cone(&[*bad]);
+            let size = self
+                .nodes_range()
+                .filter(|id| cone[*id] && self.nodes[*id].is_and())
+                .count();
+            stats.bad_cones.push(size);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Aig, AigEdge};
+
+    #[test]
+    fn test_stats() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let inputs = aig.inputs.clone();
+        let xnor = aig.new_equal_node(inputs[0].into(), AigEdge::from(inputs[1]));
+        aig.add_output(xnor);
+        let stats = aig.stats();
+        assert_eq!(stats.depth, aig.depth());
+        assert_eq!(stats.levels.iter().sum::<usize>(), aig.num_ands);
+        assert_eq!(stats.mffcs.values().sum::<usize>(), aig.num_ands);
+        assert!(stats.num_xors >= 1);
+        let json = stats.to_json();
+        assert!(json.starts_with("{\"inputs\": 3, "));
+        assert!(json.contains(&format!("\"ands\": {}", aig.num_ands)));
+    }
+}
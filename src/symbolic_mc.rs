@@ -10,6 +10,11 @@ pub static mut TOTAL_FRAIG_ADD_SAT: usize = 0;
 pub static mut TOTAL_FE_MERGE_NODE: usize = 0;
 pub static mut TOTAL_STASH_GET: usize = 0;
 pub static mut TOTAL_FRAIG_LAZY_CHECKED_WITHOUT_SAT: usize = 0;
+
+/// words kept per node by FRAIG during model checking unless the design sets
+/// its own limit, the counterexamples would grow the words without bound.
+const SYMBOLIC_MC_WORD_LIMIT: usize = 4096;
+
 struct EliminateOrder {
     inputs: Vec<AigNodeId>,
 }
@@ -74,7 +79,14 @@ impl Aig {
         dbg!(self.fraig.as_ref().unwrap().nword());
     }
 
+    fn symbolic_mc_word_limit(&mut self) {
+        if self.simulation_word_limit().is_none() {
+            self.set_simulation_word_limit(Some(SYMBOLIC_MC_WORD_LIMIT));
+        }
+    }
+
     pub fn symbolic_mc_back(&mut self) -> bool {
+        self.symbolic_mc_word_limit();
         if self.bads.is_empty() {
             return true;
         }
@@ -120,6 +132,7 @@ impl Aig {
     }
 
     pub fn symbolic_mc(&mut self) -> bool {
+        self.symbolic_mc_word_limit();
         let mut reach = self.latch_init_equation();
         let mut frontier = reach;
         let mut inputs = self.inputs.clone();
@@ -0,0 +1,116 @@
+use crate::{evaluate, Aig, AigEdge};
+
+/// steps of plain ternary simulation before states are joined to force a
+/// fixpoint.
+const TERNARY_WIDEN_STEPS: usize = 64;
+
+/// Value of a latch over all the states reached by ternary simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TernaryLatch {
+    /// the latch holds the value in every reachable state.
+    Constant(bool),
+    /// the latch is X forever once the simulation settles.
+    StuckX,
+    Varying,
+}
+
+fn ternary_join(x: &[Option<bool>], y: &[Option<bool>]) -> Vec<Option<bool>> {
+    x.iter()
+        .zip(y.iter())
+        .map(|(x, y)| if x == y { *x } else { None })
+        .collect()
+}
+
+impl Aig {
+    fn ternary_step(&self, state: &[Option<bool>]) -> Vec<Option<bool>> {
+        let values: Vec<AigEdge> = self
+            .latchs
+            .iter()
+            .zip(state.iter())
+            .filter_map(|(l, v)| v.map(|v| AigEdge::new(l.input, !v)))
+            .collect();
+        let value = evaluate(&self.nodes, &values);
+        self.latchs
+            .iter()
+            .map(|l| value[l.next.node_id()].map(|v| v ^ l.next.compl()))
+            .collect()
+    }
+
+    /// Sequential ternary simulation from the init state with every input X,
+    /// until a state repeats. After `TERNARY_WIDEN_STEPS` steps the new
+    /// states are joined with the previous ones, which always reaches a
+    /// fixpoint. The visited states cover every reachable state.
+    pub fn ternary_simulate(&self) -> Vec<TernaryLatch> {
+        let mut states: Vec<Vec<Option<bool>>> =
+            vec![self.latchs.iter().map(|l| Some(l.init)).collect()];
+        let cycle = loop {
+            let last = states.last().unwrap();
+            let mut next = self.ternary_step(last);
+            if states.len() > TERNARY_WIDEN_STEPS {
+                next = ternary_join(last, &next);
+            }
+            if let Some(pos) = states.iter().position(|s| *s == next) {
+                break pos;
+            }
+            states.push(next);
+        };
+        (0..self.latchs.len())
+            .map(|i| {
+                let init = states[0][i];
+                if states.iter().all(|s| s[i].is_some() && s[i] == init) {
+                    TernaryLatch::Constant(init.unwrap())
+                } else if states[cycle..].iter().all(|s| s[i].is_none()) {
+                    TernaryLatch::StuckX
+                } else {
+                    TernaryLatch::Varying
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the latchs found constant by ternary simulation with their
+    /// value and remove them. The FRAIG state is dropped. Returns the number
+    /// of removed latchs.
+    pub fn ternary_reduce(&mut self) -> usize {
+        let ternary = self.ternary_simulate();
+        let mut replace = vec![None; self.num_nodes()];
+        for (latch, value) in self.latchs.iter().zip(ternary.iter()) {
+            if let TernaryLatch::Constant(v) = value {
+                replace[latch.input] = Some(AigEdge::constant_edge(*v));
+            }
+        }
+        let num_latchs = self.latchs.len();
+        self.retain_latchs(|l| replace[l.input].is_none());
+        let removed = num_latchs - self.latchs.len();
+        if removed > 0 {
+            self.rebuild(&replace);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TernaryLatch;
+    use crate::{Aig, AigEdge};
+
+    #[test]
+    fn test_ternary_simulate() {
+        let mut aig = Aig::from_file("aigs/ternary.aag").unwrap();
+        assert_eq!(
+            aig.ternary_simulate(),
+            [
+                TernaryLatch::Constant(false),
+                TernaryLatch::StuckX,
+                TernaryLatch::Constant(true),
+                TernaryLatch::Varying,
+            ]
+        );
+        assert_eq!(aig.ternary_reduce(), 2);
+        assert_eq!(aig.latchs.len(), 2);
+        assert_eq!(aig.symbols().latch(0), Some("s1"));
+        assert_eq!(aig.symbols().latch(1), Some("s3"));
+        assert_eq!(aig.num_ands, 0);
+        assert_eq!(aig.outputs[0], AigEdge::constant_edge(false));
+    }
+}
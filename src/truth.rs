@@ -0,0 +1,250 @@
+use crate::{
+    cut::{TRUTH_MAX_VARS, TRUTH_VARS},
+    simulate::{SimulationWord, SIMULATION_FALSE_WORD},
+    Aig, AigEdge, AigNodeId,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter, Result},
+    ops::{BitAnd, BitOr, BitXor, Not},
+};
+
+pub const TRUTH_WORDS_MAX_VARS: usize = 16;
+
+/// A bit-packed truth table over at most `TRUTH_WORDS_MAX_VARS` variables,
+/// bit m is the value under the assignment where variable i is bit i of m.
+/// Bits past `2^nvar` are kept zero so tables compare by value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TruthWords {
+    nvar: usize,
+    words: Vec<SimulationWord>,
+}
+
+impl TruthWords {
+    fn nword(nvar: usize) -> usize {
+        1 << nvar.saturating_sub(TRUTH_MAX_VARS)
+    }
+
+    fn mask(&self) -> SimulationWord {
+        if self.nvar < TRUTH_MAX_VARS {
+            (1 << (1 << self.nvar)) - 1
+        } else {
+            !0
+        }
+    }
+
+    pub fn constant(nvar: usize, value: bool) -> Self {
+        assert!(nvar <= TRUTH_WORDS_MAX_VARS);
+        let mut ret = Self {
+            nvar,
+            words: vec![SIMULATION_FALSE_WORD; Self::nword(nvar)],
+        };
+        if value {
+            ret = !ret;
+        }
+        ret
+    }
+
+    /// the projection function of variable `var`.
+    pub fn var(nvar: usize, var: usize) -> Self {
+        assert!(var < nvar && nvar <= TRUTH_WORDS_MAX_VARS);
+        let mut ret = Self::constant(nvar, false);
+        let mask = ret.mask();
+        for (i, word) in ret.words.iter_mut().enumerate() {
+            *word = if var < TRUTH_MAX_VARS {
+                TRUTH_VARS[var] & mask
+            } else if i >> (var - TRUTH_MAX_VARS) & 1 == 1 {
+                !0
+            } else {
+                0
+            };
+        }
+        ret
+    }
+
+    pub fn nvar(&self) -> usize {
+        self.nvar
+    }
+
+    pub fn words(&self) -> &[SimulationWord] {
+        &self.words
+    }
+
+    pub fn value(&self, minterm: usize) -> bool {
+        self.words[minterm / SimulationWord::BITS as usize]
+            >> (minterm % SimulationWord::BITS as usize)
+            & 1
+            == 1
+    }
+
+    pub fn is_constant(&self) -> Option<bool> {
+        if self.words.iter().all(|w| *w == 0) {
+            Some(false)
+        } else if *self == Self::constant(self.nvar, true) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// Some(false) if the tables are equal, Some(true) if one is the
+    /// complement of the other, None otherwise.
+    pub fn compare(&self, other: &Self) -> Option<bool> {
+        assert_eq!(self.nvar, other.nvar);
+        if self == other {
+            Some(false)
+        } else if self
+            .words
+            .iter()
+            .zip(other.words.iter())
+            .all(|(a, b)| *a == !*b & self.mask())
+        {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    pub fn depends_on(&self, var: usize) -> bool {
+        assert!(var < self.nvar);
+        if var < TRUTH_MAX_VARS {
+            let shift = 1 << var;
+            self.words
+                .iter()
+                .any(|w| (w & !TRUTH_VARS[var]) != (w & TRUTH_VARS[var]) >> shift)
+        } else {
+            let step = 1 << (var - TRUTH_MAX_VARS);
+            (0..self.words.len())
+                .filter(|i| i & step == 0)
+                .any(|i| self.words[i] != self.words[i + step])
+        }
+    }
+
+    /// the variables the function depends on.
+    pub fn support(&self) -> Vec<usize> {
+        (0..self.nvar).filter(|v| self.depends_on(*v)).collect()
+    }
+}
+
+impl Not for TruthWords {
+    type Output = Self;
+
+    fn not(mut self) -> Self::Output {
+        let mask = self.mask();
+        for word in self.words.iter_mut() {
+            *word = !*word & mask;
+        }
+        self
+    }
+}
+
+macro_rules! truth_words_binary_op {
+    ($trait:ident, $fn:ident, $op:tt) => {
+        impl $trait for TruthWords {
+            type Output = Self;
+
+            fn $fn(mut self, rhs: Self) -> Self::Output {
+                assert_eq!(self.nvar, rhs.nvar);
+                for (word, rhs) in self.words.iter_mut().zip(rhs.words.iter()) {
+                    *word $op *rhs;
+                }
+                self
+            }
+        }
+    };
+}
+
+truth_words_binary_op!(BitAnd, bitand, &=);
+truth_words_binary_op!(BitOr, bitor, |=);
+truth_words_binary_op!(BitXor, bitxor, ^=);
+
+impl Display for TruthWords {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let digits = ((1 << self.nvar) / 4).max(1);
+        for word in self.words.iter().rev() {
+            write!(f, "{:0>1$x}", word, digits.min(16))?;
+        }
+        Ok(())
+    }
+}
+
+impl Aig {
+    /// Exact function of `root` over the `leaves`, leaf i is variable i. The
+    /// leaves must cut `root` from the inputs.
+    pub fn truth_table(&self, root: AigEdge, leaves: &[AigNodeId]) -> TruthWords {
+        let nvar = leaves.len();
+        assert!(nvar <= TRUTH_WORDS_MAX_VARS);
+        let mut words: HashMap<AigNodeId, TruthWords> = HashMap::new();
+        words.insert(0, TruthWords::constant(nvar, false));
+        for (var, leaf) in leaves.iter().enumerate() {
+            words.insert(*leaf, TruthWords::var(nvar, var));
+        }
+        let mut cone = HashSet::new();
+        let mut stack = vec![root.node_id()];
+        while let Some(id) = stack.pop() {
+            if words.contains_key(&id) || !cone.insert(id) {
+                continue;
+            }
+            assert!(self.nodes[id].is_and(), "leaves do not cut the cone");
+            stack.push(self.nodes[id].fanin0().node_id());
+            stack.push(self.nodes[id].fanin1().node_id());
+        }
+        let mut cone: Vec<AigNodeId> = cone.into_iter().collect();
+        cone.sort();
+        for id in cone {
+            let fanin0 = self.nodes[id].fanin0();
+            let fanin1 = self.nodes[id].fanin1();
+            let truth0 = &words[&fanin0.node_id()];
+            let truth1 = &words[&fanin1.node_id()];
+            let mask = truth0.mask();
+            let edge_word = |word: SimulationWord, edge: AigEdge| {
+                if edge.compl() {
+                    !word & mask
+                } else {
+                    word
+                }
+            };
+            let truth = TruthWords {
+                nvar,
+                words: truth0
+                    .words
+                    .iter()
+                    .zip(truth1.words.iter())
+                    .map(|(w0, w1)| edge_word(*w0, fanin0) & edge_word(*w1, fanin1))
+                    .collect(),
+            };
+            words.insert(id, truth);
+        }
+        let truth = words.remove(&root.node_id()).unwrap();
+        if root.compl() {
+            !truth
+        } else {
+            truth
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TruthWords;
+    use crate::{Aig, AigEdge};
+
+    #[test]
+    fn test_truth_table() {
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let inputs = aig.inputs.clone();
+        let x = aig.new_and_node(inputs[0].into(), inputs[1].into());
+        let y = aig.new_and_node(!AigEdge::from(inputs[0]), !AigEdge::from(inputs[1]));
+        let xnor = aig.new_or_node(x, y);
+        let truth = aig.truth_table(xnor, &inputs);
+        assert_eq!(truth.nvar(), inputs.len());
+        assert_eq!(truth.support(), [0, 1]);
+        let expect = TruthWords::var(inputs.len(), 0);
+        let expect = !(expect.clone() ^ TruthWords::var(inputs.len(), 1));
+        assert_eq!(truth.compare(&expect), Some(false));
+        assert_eq!(truth.compare(&!expect), Some(true));
+        let truth = aig.truth_table(x, &inputs[..2]);
+        assert_eq!(truth.words(), [0x8]);
+        assert_eq!(format!("{}", truth), "8");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{evaluate, simulate::SimulationCex, Aig, AigEdge, AigNodeId};
+use std::io::{self, Write};
+
+/// the short VCD identifier of the i-th signal, in base 94 over the
+/// printable characters.
+fn vcd_identifier(mut i: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return id;
+        }
+        i -= 1;
+    }
+}
+
+/// a VCD reference name, whitespace is not allowed.
+fn vcd_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+impl Aig {
+    /// the dumped signals, the inputs, latchs, outputs, bad states and the
+    /// chosen internal nodes, named by their symbols when available.
+    fn vcd_signals(&self, nodes: &[AigNodeId]) -> Vec<(String, AigEdge)> {
+        let name = |symbol: Option<&str>, default: String| vcd_name(symbol.unwrap_or(&default));
+        let mut signals = Vec::new();
+        for (i, input) in self.inputs.iter().enumerate() {
+            let name = name(self.symbols.input(i), format!("i{}", i));
+            signals.push((name, AigEdge::from(*input)));
+        }
+        for (i, latch) in self.latchs.iter().enumerate() {
+            let name = name(self.symbols.latch(i), format!("l{}", i));
+            signals.push((name, AigEdge::from(latch.input)));
+        }
+        for (i, output) in self.outputs.iter().enumerate() {
+            let name = name(self.symbols.output(i), format!("o{}", i));
+            signals.push((name, *output));
+        }
+        for (i, bad) in self.bads.iter().enumerate() {
+            let name = name(self.symbols.bad(i), format!("b{}", i));
+            signals.push((name, *bad));
+        }
+        for node in nodes {
+            signals.push((format!("n{}", node), AigEdge::from(*node)));
+        }
+        signals
+    }
+
+    /// Write a cycle trace from the init state as VCD, `trace[k][i]` is the
+    /// value of input i in cycle k, like the inputs of a `SimulationCex`.
+    /// The inputs, latchs, outputs, bad states and the chosen internal
+    /// `nodes` are dumped, one time unit per cycle.
+    pub fn write_vcd<W: Write>(
+        &self,
+        out: &mut W,
+        trace: &[Vec<bool>],
+        nodes: &[AigNodeId],
+    ) -> io::Result<()> {
+        let signals = self.vcd_signals(nodes);
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module top $end")?;
+        for (i, (name, _)) in signals.iter().enumerate() {
+            writeln!(out, "$var wire 1 {} {} $end", vcd_identifier(i), name)?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        let mut state: Vec<bool> = self.latchs.iter().map(|l| l.init).collect();
+        let mut prev: Vec<Option<bool>> = vec![None; signals.len()];
+        for (cycle, inputs) in trace.iter().enumerate() {
+            assert_eq!(inputs.len(), self.inputs.len());
+            let mut values: Vec<AigEdge> = self
+                .inputs
+                .iter()
+                .zip(inputs.iter())
+                .map(|(id, v)| AigEdge::new(*id, !*v))
+                .collect();
+            for (latch, v) in self.latchs.iter().zip(state.iter()) {
+                values.push(AigEdge::new(latch.input, !*v));
+            }
+            let value = evaluate(&self.nodes, &values);
+            let edge_value = |e: AigEdge| value[e.node_id()].unwrap() ^ e.compl();
+            writeln!(out, "#{}", cycle)?;
+            for (i, (_, edge)) in signals.iter().enumerate() {
+                let v = edge_value(*edge);
+                if prev[i] != Some(v) {
+                    writeln!(out, "{}{}", v as u8, vcd_identifier(i))?;
+                    prev[i] = Some(v);
+                }
+            }
+            state = self.latchs.iter().map(|l| edge_value(l.next)).collect();
+        }
+        writeln!(out, "#{}", trace.len())
+    }
+
+    /// Write the failing trace of sequential simulation as VCD, see
+    /// `write_vcd`.
+    pub fn write_cex_vcd<W: Write>(
+        &self,
+        out: &mut W,
+        cex: &SimulationCex,
+        nodes: &[AigNodeId],
+    ) -> io::Result<()> {
+        self.write_vcd(out, &cex.inputs, nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::vcd_identifier;
+    use crate::Aig;
+
+    #[test]
+    fn test_write_vcd() {
+        assert_eq!(vcd_identifier(0), "!");
+        assert_eq!(vcd_identifier(93), "~");
+        assert_eq!(vcd_identifier(94), "!!");
+        let aig = Aig::from_file("aigs/counter.aag").unwrap();
+        let trace = vec![vec![true]; 8];
+        let mut out = Vec::new();
+        aig.write_vcd(&mut out, &trace, &[]).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+        assert!(vcd.contains("$var wire 1 ! en $end"));
+        assert!(vcd.contains("$var wire 1 $ count_2 $end"));
+        assert!(vcd.contains("$var wire 1 % b0 $end"));
+        let rise = vcd.find("\n1%\n").unwrap();
+        let cycle = vcd[..rise].rfind('#').unwrap();
+        assert!(vcd[cycle..].starts_with("#7\n"));
+    }
+
+    #[test]
+    fn test_write_cex_vcd() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        let cex = aig.sequential_simulate(4, 64).unwrap();
+        let mut out = Vec::new();
+        aig.write_cex_vcd(&mut out, &cex, &[]).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+        assert!(!vcd.contains('x'));
+        let rise = vcd.find("\n1%\n").unwrap();
+        let cycle = vcd[..rise].rfind('#').unwrap();
+        let last = format!("#{}\n", cex.inputs.len() - 1);
+        assert!(vcd[cycle..].starts_with(&last));
+    }
+}
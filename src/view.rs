@@ -0,0 +1,222 @@
+use crate::{
+    aiger::AigSymbols,
+    evaluate, fanin_logic_cone, fanout_logic_cone,
+    sat::{self, SatSolver},
+    simulate::{
+        simulate_nodes, RandomWordGenerator, SimdSimulationWord, Simulation, SimulationOptions,
+        SimulationWords,
+    },
+    Aig, AigEdge, AigLatch, AigNode, AigNodeId,
+};
+use rand::rngs::StdRng;
+use std::ops::Index;
+
+/// An immutable snapshot of the graph, latchs, outputs, bad states and their
+/// symbols, with the random generator and simulation options of the design.
+/// It holds no solver or FRAIG state, so it is `Send` and `Sync` and can be
+/// shared between threads behind an `Arc`.
+#[derive(Debug, Clone)]
+pub struct AigView {
+    nodes: Vec<AigNode>,
+    inputs: Vec<AigNodeId>,
+    latchs: Vec<AigLatch>,
+    outputs: Vec<AigEdge>,
+    bads: Vec<AigEdge>,
+    num_ands: usize,
+    rng: StdRng,
+    symbols: AigSymbols,
+    simulation_options: SimulationOptions,
+}
+
+impl AigView {
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn num_ands(&self) -> usize {
+        self.num_ands
+    }
+
+    pub fn inputs(&self) -> &[AigNodeId] {
+        &self.inputs
+    }
+
+    pub fn latchs(&self) -> &[AigLatch] {
+        &self.latchs
+    }
+
+    pub fn outputs(&self) -> &[AigEdge] {
+        &self.outputs
+    }
+
+    pub fn bads(&self) -> &[AigEdge] {
+        &self.bads
+    }
+
+    pub fn symbols(&self) -> &AigSymbols {
+        &self.symbols
+    }
+
+    pub fn simulation_options(&self) -> SimulationOptions {
+        self.simulation_options
+    }
+
+    pub fn fanin_logic_cone<'a, I: IntoIterator<Item = &'a AigEdge>>(&self, logic: I) -> Vec<bool> {
+        fanin_logic_cone(&self.nodes, logic)
+    }
+
+    pub fn fanout_logic_cone(&self, logic: AigEdge) -> Vec<bool> {
+        fanout_logic_cone(&self.nodes, logic)
+    }
+
+    pub fn evaluate(&self, values: &[AigEdge]) -> Vec<Option<bool>> {
+        evaluate(&self.nodes, values)
+    }
+
+    /// Random bit-parallel simulation of `nsimd_word` simd words per node.
+    /// The words of the inputs and latch inputs come from the generator of
+    /// the caller, so each thread brings its own.
+    pub fn new_simulation(&self, nsimd_word: usize, gen: &mut RandomWordGenerator) -> Simulation {
+        let nwords = nsimd_word * SimdSimulationWord::LANES;
+        simulate_nodes(&self.nodes, nsimd_word, |_| {
+            let sim = SimulationWords::new(nwords, gen);
+            (0..nwords).map(|i| sim[i]).collect()
+        })
+    }
+
+    /// A new SAT solver loaded with the nodes of the view, owned by the
+    /// calling thread.
+    pub fn new_sat_solver(&self) -> Box<dyn SatSolver> {
+        let mut solver = Box::new(sat::abc_glucose::Solver::new());
+        sat::load_nodes(solver.as_mut(), &self.nodes);
+        solver
+    }
+
+    /// A mutable design built from the view, without FRAIG state. It keeps
+    /// the symbols, random generator and simulation options of the view.
+    pub fn to_aig(&self) -> Aig {
+        let mut aig = Aig {
+            nodes: self.nodes.clone(),
+            inputs: self.inputs.clone(),
+            latchs: self.latchs.clone(),
+            outputs: self.outputs.clone(),
+            bads: self.bads.clone(),
+            num_ands: self.num_ands,
+            fraig: None,
+            sat_solver: Box::new(sat::abc_glucose::Solver::new()),
+            rng: self.rng.clone(),
+            symbols: self.symbols.clone(),
+            simulation_options: self.simulation_options,
+        };
+        aig.setup_sat_solver();
+        aig
+    }
+}
+
+impl Index<AigNodeId> for AigView {
+    type Output = AigNode;
+
+    fn index(&self, index: AigNodeId) -> &Self::Output {
+        &self.nodes[index]
+    }
+}
+
+impl Aig {
+    pub fn view(&self) -> AigView {
+        AigView {
+            nodes: self.nodes.clone(),
+            inputs: self.inputs.clone(),
+            latchs: self.latchs.clone(),
+            outputs: self.outputs.clone(),
+            bads: self.bads.clone(),
+            num_ands: self.num_ands,
+            rng: self.rng.clone(),
+            symbols: self.symbols.clone(),
+            simulation_options: self.simulation_options,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AigView;
+    use crate::{
+        simulate::{RandomWordGenerator, Simulation},
+        Aig, AigEdge,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::{sync::Arc, thread};
+
+    fn assert_same_words(x: &Simulation, y: &Simulation, num_nodes: usize) {
+        assert_eq!(x.nword(), y.nword());
+        for id in 0..num_nodes {
+            for i in 0..x.nword() {
+                assert_eq!(x[id][i], y[id][i]);
+            }
+        }
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_view() {
+        assert_send_sync::<AigView>();
+        let mut aig = Aig::from_file("aigs/cec1.aag").unwrap();
+        let view = Arc::new(aig.view());
+        let inputs: Vec<AigEdge> = aig.inputs.iter().map(|i| AigEdge::new(*i, true)).collect();
+        let expect = aig.evaluate(&inputs);
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let view = view.clone();
+                let inputs = inputs.clone();
+                thread::spawn(move || {
+                    let mut solver = view.new_sat_solver();
+                    let outputs = view.outputs();
+                    let equal = solver.equivalence_check(outputs[0], outputs[1]).is_none();
+                    (view.evaluate(&inputs), equal)
+                })
+            })
+            .collect();
+        for handle in handles {
+            let (value, equal) = handle.join().unwrap();
+            assert_eq!(value, expect);
+            assert!(equal);
+        }
+        assert_eq!(view.to_aig().num_ands, aig.num_ands);
+    }
+
+    #[test]
+    fn test_view_simulation() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.set_rng_seed(3);
+        let view = Arc::new(aig.view());
+        let expect = aig.new_simulation(2);
+        let seed: u64 = StdRng::seed_from_u64(3).gen();
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let view = view.clone();
+                thread::spawn(move || view.new_simulation(2, &mut RandomWordGenerator::new(seed)))
+            })
+            .collect();
+        for handle in handles {
+            assert_same_words(&handle.join().unwrap(), &expect, aig.num_nodes());
+        }
+    }
+
+    #[test]
+    fn test_view_to_aig() {
+        let mut aig = Aig::from_file("aigs/counter.aag").unwrap();
+        aig.set_rng_seed(7);
+        aig.set_simulation_word_limit(Some(128));
+        aig.set_simulation_threads(2);
+        let view = aig.view();
+        assert_eq!(view.symbols().latch(0), aig.symbols().latch(0));
+        let mut copy = view.to_aig();
+        assert_eq!(copy.symbols().input(0), aig.symbols().input(0));
+        assert_eq!(copy.symbols().latch(2), aig.symbols().latch(2));
+        assert_eq!(copy.simulation_word_limit(), Some(128));
+        assert_eq!(copy.simulation_threads(), 2);
+        let (x, y) = (aig.new_simulation(1), copy.new_simulation(1));
+        assert_same_words(&x, &y, aig.num_nodes());
+    }
+}